@@ -1,7 +1,7 @@
 use crossbeam::utils::CachePadded;
 use std::sync::atomic::{AtomicU64, Ordering};
 
-/// [`rayon`]-aware counter.  Starts at zero, counts up.
+/// [`rayon`]-aware counter.  Starts at zero, usually counts up.
 ///
 /// This is **only** for user feedback in the middle of a long multi-core
 /// computation.
@@ -29,14 +29,73 @@ impl Counter {
     }
 
     pub fn get(&self) -> u64 {
+        // Internal counters wrap, since a decrement can land on a different
+        // counter than the increment it backs out.  The sum is still right.
         self.0
             .iter()
             .map(|atomic| atomic.load(Ordering::Relaxed))
-            .sum()
+            .fold(0, u64::wrapping_add)
     }
 
     pub fn increment(&self) {
-        let idx = rayon::current_thread_index().unwrap_or(0);
-        self.0[idx].fetch_add(1, Ordering::Relaxed);
+        self.add(1);
+    }
+
+    pub fn add(&self, n: u64) {
+        self.0[Self::index()].fetch_add(n, Ordering::Relaxed);
+    }
+
+    /// Back out one earlier increment.  The total must not go below zero.
+    pub fn decrement(&self) {
+        self.0[Self::index()].fetch_sub(1, Ordering::Relaxed);
+    }
+
+    /// Replace the total, for example to restore saved progress.
+    ///
+    /// This is not atomic as a whole:  increments which race with it might be
+    /// lost.
+    pub fn set(&self, n: u64) {
+        for atomic in &self.0[1..] {
+            atomic.store(0, Ordering::Relaxed);
+        }
+        self.0[0].store(n, Ordering::Relaxed);
+    }
+
+    fn index() -> usize {
+        rayon::current_thread_index().unwrap_or(0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Counter;
+
+    #[test]
+    fn concurrent_add() {
+        let counter = Counter::zero();
+
+        std::thread::scope(|scope| {
+            for n in 1..=8 {
+                let counter = &counter;
+                scope.spawn(move || {
+                    for _ in 0..1000 {
+                        counter.add(n);
+                    }
+                });
+            }
+        });
+        assert_eq!(counter.get(), 36 * 1000);
+
+        // Decrements on other rayon threads still cancel out.
+        rayon::scope(|scope| {
+            for _ in 0..100 {
+                scope.spawn(|_| counter.decrement());
+            }
+        });
+        assert_eq!(counter.get(), 36 * 1000 - 100);
+
+        counter.set(5);
+        counter.increment();
+        assert_eq!(counter.get(), 6);
     }
 }
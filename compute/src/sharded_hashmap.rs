@@ -47,6 +47,36 @@ where
     V: Send,
     H: BuildHasher;
 
+/// Distribution of entries over the shards of a [`ShardedHashMap`].
+///
+/// See [`ShardedHashMap::load_factor_stats`].
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ShardStats {
+    pub min: usize,
+    pub max: usize,
+    pub mean: f64,
+    pub stddev: f64,
+}
+
+impl ShardStats {
+    fn from_lens(lens: &[usize]) -> ShardStats {
+        let n = lens.len() as f64;
+        let mean = lens.iter().sum::<usize>() as f64 / n;
+        let variance = lens
+            .iter()
+            .map(|&len| (len as f64 - mean).powi(2))
+            .sum::<f64>()
+            / n;
+
+        ShardStats {
+            min: lens.iter().copied().min().unwrap_or(0),
+            max: lens.iter().copied().max().unwrap_or(0),
+            mean,
+            stddev: variance.sqrt(),
+        }
+    }
+}
+
 fn hash<T: Hash, H: BuildHasher>(key: T, h: &H) -> u64 {
     let mut state = h.build_hasher();
     key.hash(&mut state);
@@ -94,6 +124,23 @@ impl<K: Hash + Eq + Send, V: Send, const SHARD_SIZE: usize, H: BuildHasher>
             .sum()
     }
 
+    /// Number of entries in each shard, in shard order.
+    pub fn shard_lens(&mut self) -> Vec<usize> {
+        self.0
+            .iter_mut()
+            .map(|mutex| mutex.get_mut())
+            .map(|shard| shard.len())
+            .collect()
+    }
+
+    /// Summarize how evenly entries are spread over shards.
+    ///
+    /// A shard much larger than the mean is a hot shard, and will see more
+    /// lock contention than the others.
+    pub fn load_factor_stats(&mut self) -> ShardStats {
+        ShardStats::from_lens(&self.shard_lens())
+    }
+
     pub fn iter_mut(&mut self) -> impl Iterator<Item = (&K, &mut V)> {
         self.0.iter_mut().map(|mutex| mutex.get_mut()).flatten()
     }
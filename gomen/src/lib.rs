@@ -1,14 +1,19 @@
 use js_sys::Uint8Array;
 use miniserde::{json, Serialize};
 use queue::Bag;
-use std::{collections::HashSet, io::Cursor};
+use std::{
+    collections::HashSet,
+    io::{self, Cursor, Write},
+    sync::OnceLock,
+};
 use wasm_bindgen::prelude::wasm_bindgen;
 
 use srs_4l::{
     base64::{base64_decode, base64_encode},
     board_list,
     brokenboard::BrokenBoard,
-    gameplay::{Board, Physics, Shape},
+    gameplay::{Board, Orientation, Physics, Piece, Shape},
+    vector::Placements,
 };
 
 pub mod queue;
@@ -16,43 +21,53 @@ pub mod solver;
 
 #[wasm_bindgen]
 pub struct Solver {
-    boards: HashSet<Board>,
+    /// Sorted, as read from the board list.
+    boards: Vec<Board>,
+    /// The same boards, hashed for the solver's many lookups.  Built the first
+    /// time a search needs it, so that loading stays quick.
+    hashed: OnceLock<HashSet<Board>>,
+    by_queue: bool,
 }
 
 #[wasm_bindgen]
 impl Solver {
     #[wasm_bindgen(constructor)]
     pub fn init(legal_boards: Option<Uint8Array>) -> Solver {
-        let boards: HashSet<Board> = match legal_boards {
-            Some(arr) => board_list::read(Cursor::new(&arr.to_vec()))
-                .unwrap()
-                .drain(..)
-                .collect(),
-            None => Default::default(),
-        };
+        match legal_boards {
+            Some(arr) => Solver::from_board_list(Cursor::new(&arr.to_vec())).unwrap(),
+            None => Solver::from_boards(Vec::new()),
+        }
+    }
 
-        Solver { boards }
+    /// Choose whether to order solutions by the least queue which produces
+    /// them (off by default), rather than by their bits.  See
+    /// [`solver::compute_by_queue`].
+    ///
+    /// This is a much more natural order to browse in.
+    pub fn set_by_queue(&mut self, by_queue: bool) {
+        self.by_queue = by_queue;
     }
 
+    /// Find every solution, separated by `,`.  Each is drawn as 40
+    /// characters, top row first, then `|`, then its
+    /// [encoding](BrokenBoard::encode).
+    ///
+    /// If the query can't be solved at all, for example because the queue is
+    /// too long for the field, returns `error: ` followed by a message to show
+    /// instead.  That never looks like a solution.
     pub fn solve(&self, queue: Queue, garbage: u64, can_hold: bool, physics: String) -> String {
-        let empty_boards = Default::default();
-
-        let start = BrokenBoard::from_garbage(garbage);
-
-        let legal_boards = if self.is_fast(garbage) {
-            &self.boards
-        } else {
-            &empty_boards
-        };
-
         let physics = match physics.as_ref() {
             "SRS" => Physics::SRS,
             "Jstris" => Physics::Jstris,
             "TETRIO" => Physics::Tetrio,
-            _ => return "".into(),
+            _ => return format!("error: unknown physics {:?}", physics),
         };
 
-        let solutions = solver::compute(legal_boards, &start, &queue.bags, can_hold, physics);
+        if let Err(msg) = check_fits(&queue, garbage, can_hold) {
+            return format!("error: {}", msg);
+        }
+
+        let solutions = self.compute(&queue, garbage, can_hold, physics);
         let mut str = String::new();
 
         for board in &solutions {
@@ -67,21 +82,310 @@ impl Solver {
     }
 
     pub fn is_fast(&self, garbage: u64) -> bool {
-        self.boards
-            .contains(&BrokenBoard::from_garbage(garbage).board)
+        board_list::contains(&self.boards, BrokenBoard::from_garbage(garbage).board)
+    }
+}
+
+/// One line of [`Solver::solve_jsonl`] output.
+#[derive(Serialize)]
+struct JsonSolution {
+    board: String,
+    ascii: String,
+}
+
+impl Solver {
+    /// Load the legal boards once, for native callers who solve many times.
+    ///
+    /// The list is in the format written by [`board_list::write`], like the
+    /// bundled `legal-boards.leb128`.  [`Solver::init`] does the same from a
+    /// JavaScript array.
+    pub fn from_board_list(r: impl io::Read) -> io::Result<Solver> {
+        Ok(Solver::from_boards(board_list::read(r)?))
+    }
+
+    fn from_boards(boards: Vec<Board>) -> Solver {
+        Solver {
+            boards,
+            hashed: OnceLock::new(),
+            by_queue: false,
+        }
+    }
+
+    fn compute(
+        &self,
+        queue: &Queue,
+        garbage: u64,
+        can_hold: bool,
+        physics: Physics,
+    ) -> Vec<BrokenBoard> {
+        if check_fits(queue, garbage, can_hold).is_err() {
+            return Vec::new();
+        }
+
+        let start = BrokenBoard::from_garbage(garbage);
+
+        let empty_boards = HashSet::new();
+
+        let legal_boards = if self.is_fast(garbage) {
+            self.hashed
+                .get_or_init(|| self.boards.iter().copied().collect())
+        } else {
+            &empty_boards
+        };
+
+        let config = solver::Config { can_hold, physics };
+
+        if self.by_queue {
+            solver::compute_by_queue(legal_boards, &start, &queue.bags, queue.hold, config, &[])
+                .into_iter()
+                .map(|(board, _queue)| board)
+                .collect()
+        } else {
+            solver::compute(legal_boards, &start, &queue.bags, queue.hold, config, &[])
+        }
+    }
+
+    /// Solve, writing solutions as [JSON lines](https://jsonlines.org/)
+    /// instead of one big string.
+    ///
+    /// Each line is an object with two fields:
+    ///
+    /// - `board`: the [encoded](BrokenBoard::encode) solution, as in the second
+    ///   half of each solution from [`solve`](Solver::solve)
+    /// - `ascii`: the solution drawn as 40 characters, top row first, as in the
+    ///   first half
+    ///
+    /// This is for native tools, which can process solutions one at a time
+    /// rather than splitting an enormous string.
+    ///
+    /// Fails with [`io::ErrorKind::InvalidInput`] if the queue has more pieces
+    /// than the field has room for.
+    pub fn solve_jsonl(
+        &self,
+        queue: &Queue,
+        garbage: u64,
+        can_hold: bool,
+        physics: Physics,
+        mut w: impl Write,
+    ) -> io::Result<()> {
+        check_fits(queue, garbage, can_hold)
+            .map_err(|msg| io::Error::new(io::ErrorKind::InvalidInput, msg))?;
+
+        for board in self.compute(queue, garbage, can_hold, physics) {
+            let mut solution = JsonSolution {
+                board: String::new(),
+                ascii: String::new(),
+            };
+            base64_encode(&board.encode(), &mut solution.board);
+            solver::print(&board, &mut solution.ascii);
+
+            writeln!(w, "{}", json::to_string(&solution))?;
+        }
+
+        Ok(())
+    }
+
+    /// Solve for each garbage in turn, sharing the loaded legal boards.
+    ///
+    /// The garbages are solved in parallel, and the results are in the same
+    /// order as `garbages`.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn solve_many(
+        &self,
+        garbages: &[u64],
+        queue: &Queue,
+        can_hold: bool,
+        physics: Physics,
+    ) -> Vec<Vec<BrokenBoard>> {
+        use rayon::prelude::*;
+
+        garbages
+            .par_iter()
+            .map(|&garbage| self.compute(queue, garbage, can_hold, physics))
+            .collect()
+    }
+
+    /// Find the smallest garbage that this queue cannot perfect clear.
+    ///
+    /// Garbage with fewer cells is tried first, up to `max_cells`, and garbage
+    /// with the same number of cells is tried in increasing order.  Only cell
+    /// counts which leave exactly enough room for the queue are considered,
+    /// and garbage which no queue could ever clear (see
+    /// [`Board::has_isolated_cell`] and [`Board::has_imbalanced_split`]) is
+    /// skipped.
+    ///
+    /// This runs the solver many times, so it can be very slow.
+    pub fn min_unsolvable_garbage(
+        &self,
+        queue: &Queue,
+        can_hold: bool,
+        physics: Physics,
+        max_cells: u32,
+    ) -> Option<Board> {
+        (0..=max_cells.min(40))
+            .filter(|&cells| fits_queue(cells, queue, can_hold))
+            .flat_map(candidate_garbages)
+            .find(|board| self.compute(queue, board.0, can_hold, physics).is_empty())
+    }
+
+    /// Find every garbage with exactly `cells` cells that this queue can
+    /// perfect clear, in increasing order.
+    ///
+    /// The same garbage is skipped as in [`Solver::min_unsolvable_garbage`],
+    /// and if `cells` doesn't leave exactly enough room for the queue, the
+    /// result is empty.
+    ///
+    /// This runs the solver many times, so it can be very slow.
+    pub fn solvable_garbages(
+        &self,
+        cells: u32,
+        queue: &Queue,
+        can_hold: bool,
+        physics: Physics,
+    ) -> Vec<Board> {
+        if cells > 40 || !fits_queue(cells, queue, can_hold) {
+            return Vec::new();
+        }
+
+        candidate_garbages(cells)
+            .filter(|board| !self.compute(queue, board.0, can_hold, physics).is_empty())
+            .collect()
+    }
+}
+
+/// The most pieces a queue can have on top of garbage with `cells` cells.
+///
+/// Every piece but the last must be placed.  The solver leaves the last one
+/// unplaced if there's no room for it, whether it's in hold or never drawn.
+fn max_pieces(cells: u32) -> u32 {
+    (40 - cells) / 4 + 1
+}
+
+/// Whether garbage with `cells` cells leaves exactly enough room for `queue`.
+fn fits_queue(cells: u32, queue: &Queue, can_hold: bool) -> bool {
+    let piece_count = queue.piece_count(can_hold);
+    let max = max_pieces(cells);
+
+    // The pieces must fill the field exactly.  Either every piece is placed,
+    // or the last one is left over.
+    40 - cells == 4 * (max - 1) && (piece_count == max || piece_count + 1 == max)
+}
+
+/// Check that there's room on top of `garbage` for every piece in `queue`,
+/// except the last one, which can be left over.
+///
+/// A queue that's too long can't be solved, so this saves searching for
+/// nothing.
+fn check_fits(queue: &Queue, garbage: u64, can_hold: bool) -> Result<(), String> {
+    let max_pieces = max_pieces((garbage & 0xFFFFF_FFFFF).count_ones());
+    let piece_count = queue.piece_count(can_hold);
+
+    if piece_count > max_pieces {
+        return Err(format!(
+            "too many pieces in queue ({} total, but the field has room for {}, plus one left over)",
+            piece_count,
+            max_pieces - 1
+        ));
+    }
+
+    Ok(())
+}
+
+/// Every garbage with `cells` cells, in increasing order, except garbage which
+/// no queue could ever clear.
+fn candidate_garbages(cells: u32) -> impl Iterator<Item = Board> {
+    BitChoices::new(40, cells as u8)
+        .map(Board)
+        .filter(|board| !board.has_isolated_cell() && !board.has_imbalanced_split())
+}
+
+/// Every number below `1 << max` with exactly `count` bits set, in increasing
+/// order.
+///
+/// A long enumeration can be checkpointed with [`BitChoices::position`] and
+/// picked up again with [`BitChoices::resume_from`], so that it can be split
+/// across runs or machines.
+#[derive(Clone, Debug)]
+pub struct BitChoices {
+    max: u8,
+    next: Option<u64>,
+}
+
+impl BitChoices {
+    pub fn new(max: u8, count: u8) -> BitChoices {
+        assert!(max < 64 && count <= max);
+
+        BitChoices {
+            max,
+            next: Some((1 << count) - 1),
+        }
+    }
+
+    /// Continue an enumeration, starting with the combination whose set bits
+    /// are `bits`.
+    ///
+    /// `bits` should be a value returned from [`BitChoices::position`].
+    pub fn resume_from(max: u8, count: u8, bits: &[u8]) -> BitChoices {
+        assert!(max < 64 && count <= max);
+        assert_eq!(bits.len(), count as usize);
+        assert!(bits.iter().all(|&b| b < max));
+
+        let next = bits.iter().fold(0u64, |acc, &b| acc | 1 << b);
+        assert_eq!(next.count_ones(), count as u32);
+
+        BitChoices {
+            max,
+            next: Some(next),
+        }
+    }
+
+    /// The set bits of the next combination, in increasing order, or `None`
+    /// if the enumeration is finished.
+    pub fn position(&self) -> Option<Vec<u8>> {
+        let next = self.next.filter(|&b| b < 1 << self.max)?;
+        Some((0..self.max).filter(|&b| next & (1 << b) != 0).collect())
+    }
+}
+
+impl Iterator for BitChoices {
+    type Item = u64;
+
+    fn next(&mut self) -> Option<u64> {
+        let bits = self.next.filter(|&b| b < 1 << self.max)?;
+
+        // Gosper's hack: the next larger number with the same number of bits.
+        self.next = if bits == 0 {
+            None
+        } else {
+            let lowest = bits & bits.wrapping_neg();
+            let ripple = bits + lowest;
+            Some((((ripple ^ bits) >> 2) / lowest) | ripple)
+        };
+
+        Some(bits)
     }
 }
 
 #[wasm_bindgen]
 pub struct Queue {
     bags: Vec<Bag>,
+    hold: Option<Shape>,
 }
 
 #[wasm_bindgen]
 impl Queue {
     #[wasm_bindgen(constructor)]
     pub fn new() -> Queue {
-        Queue { bags: Vec::new() }
+        Queue {
+            bags: Vec::new(),
+            hold: None,
+        }
+    }
+
+    /// Start with this shape already in hold, as when solving from the middle
+    /// of a game.  It's ignored when solving without hold.
+    pub fn set_hold(&mut self, shape: char) {
+        self.hold = Some(parse_shape(shape).unwrap());
     }
 
     pub fn add_shape(&mut self, shape: char) {
@@ -96,6 +400,151 @@ impl Queue {
             .unwrap();
         self.bags.push(Bag::new(&shapes, count));
     }
+
+    /// List the shapes which could come next after `prefix`, among all the
+    /// sequences of shapes this queue can produce.
+    ///
+    /// The result is a string of shape names, like `"IJLOSTZ"`.  It's empty if
+    /// this queue can't start with `prefix`, or if `prefix` uses up the whole
+    /// queue.  Hold is not considered:  this is about the order pieces are
+    /// drawn in.
+    pub fn next_pieces(&self, prefix: &str) -> String {
+        let mut prefix = prefix.chars();
+
+        for bag in &self.bags {
+            let mut state = queue::QueueState(bag.full);
+
+            for _ in 0..bag.count {
+                let shape = match prefix.next() {
+                    Some(c) => parse_shape(c),
+                    None => {
+                        return Shape::ALL
+                            .iter()
+                            .filter(|&&shape| state.take(bag, shape).is_some())
+                            .map(|shape| shape.name())
+                            .collect();
+                    }
+                };
+
+                match shape.and_then(|shape| state.take(bag, shape)) {
+                    Some(new) => state = new,
+                    None => return String::new(),
+                }
+            }
+        }
+
+        String::new()
+    }
+}
+
+impl Queue {
+    /// Count the pieces in the queue, including the held piece if hold is used.
+    fn piece_count(&self, can_hold: bool) -> u32 {
+        let held = (can_hold && self.hold.is_some()) as u32;
+        self.bags.iter().map(|b| b.count as u32).sum::<u32>() + held
+    }
+}
+
+/// Find every board that can be reached from `start` using exactly this queue.
+///
+/// Unlike [`Solver::solve`], this does not look for perfect clears.  It answers
+/// "where can I possibly end up with this queue?"  See [`solver::reachable`].
+pub fn queue_reachable_boards(
+    start: Board,
+    queue: &Queue,
+    can_hold: bool,
+    physics: Physics,
+) -> HashSet<Board> {
+    let config = solver::Config { can_hold, physics };
+
+    let bags = solver::with_initial_hold(&queue.bags, queue.hold, config);
+    solver::reachable(start, &bags, config)
+}
+
+/// Count how many concrete queues a pattern like `T[IJLOSZ]3*2` expands to,
+/// without expanding it.
+///
+/// The pattern syntax is the same as the web worker's: single shapes, bags in
+/// brackets, and `*` for all seven shapes, each optionally followed by how
+/// many pieces to draw.  Anything else is ignored.
+///
+/// The count is a float, since it can easily be too large for an integer.
+#[wasm_bindgen]
+pub fn estimate_queue_count(pattern: &str) -> f64 {
+    let pattern: Vec<char> = pattern.chars().collect();
+    let mut total = 1.0;
+    let mut i = 0;
+
+    let take_count = |i: &mut usize| {
+        let start = *i;
+        while *i < pattern.len() && pattern[*i].is_ascii_digit() {
+            *i += 1;
+        }
+        let digits: String = pattern[start..*i].iter().collect();
+        match digits.parse() {
+            Ok(0) | Err(_) => 1,
+            Ok(count) => count,
+        }
+    };
+
+    while i < pattern.len() {
+        if parse_shape(pattern[i]).is_some() {
+            i += 1;
+        } else if pattern[i] == '*' {
+            i += 1;
+            total *= bag_queue_count(&Shape::ALL, take_count(&mut i));
+        } else if pattern[i] == '[' {
+            let len = pattern[i + 1..]
+                .iter()
+                .take_while(|&&c| parse_shape(c).is_some())
+                .count();
+
+            if len == 0 || pattern.get(i + 1 + len) != Some(&']') {
+                i += 1;
+                continue;
+            }
+
+            let shapes: Vec<Shape> = pattern[i + 1..i + 1 + len]
+                .iter()
+                .filter_map(|&c| parse_shape(c))
+                .collect();
+            i += len + 2;
+            total *= bag_queue_count(&shapes, take_count(&mut i));
+        } else {
+            i += 1;
+        }
+    }
+
+    total
+}
+
+/// Count the distinct sequences of `count` shapes which can be drawn from a
+/// bag.  Shapes may be repeated in the bag.
+fn bag_queue_count(shapes: &[Shape], count: usize) -> f64 {
+    let mut multiplicity = [0; 7];
+    for &shape in shapes {
+        multiplicity[shape as usize] += 1;
+    }
+
+    // Exponential generating function: the product over shapes of
+    // (1 + x + x²/2! + … + xᵐ/m!), where m is the shape's multiplicity.
+    let mut poly = vec![1.0];
+    for m in multiplicity {
+        let mut next = vec![0.0; poly.len() + m];
+        for (j, &coefficient) in poly.iter().enumerate() {
+            let mut term = coefficient;
+            for a in 0..=m {
+                if a > 0 {
+                    term /= a as f64;
+                }
+                next[j + a] += term;
+            }
+        }
+        poly = next;
+    }
+
+    let factorial: f64 = (1..=count).map(|n| n as f64).product();
+    poly.get(count).copied().unwrap_or(0.0) * factorial
 }
 
 fn parse_shape(shape: char) -> Option<Shape> {
@@ -111,13 +560,27 @@ fn parse_shape(shape: char) -> Option<Shape> {
     }
 }
 
+#[cfg(target_arch = "wasm32")]
 #[wasm_bindgen]
 extern "C" {
     pub fn progress(piece_count: usize, stage: usize, board_idx: usize, board_total: usize);
 }
 
+/// Native builds have no web worker to report progress to.
+#[cfg(not(target_arch = "wasm32"))]
+pub fn progress(_piece_count: usize, _stage: usize, _board_idx: usize, _board_total: usize) {}
+
+/// Describe a solution:  its picture, then `|`, then the queues which can build
+/// it without hold, then `|`, then the queues which can build it with hold.
+///
+/// Finding the queues with hold is slow, so it's skipped unless
+/// `include_with_hold` is set, and the last field is left empty.  Returns an
+/// empty string if the solution can't be decoded or built.
+///
+/// If the solution has too many queues to find them all, only some are
+/// listed, and a fourth field `partial` is added after them.
 #[wasm_bindgen]
-pub fn solution_info(encoded: &str) -> String {
+pub fn solution_info(encoded: &str, include_with_hold: bool) -> String {
     let mut ret = "".to_string();
 
     let bits = match base64_decode(encoded) {
@@ -130,11 +593,24 @@ pub fn solution_info(encoded: &str) -> String {
         None => return ret,
     };
 
+    // The board came from a URL, so it might not be buildable at all.  This is
+    // much cheaper to check than finding every queue.
+    if !board.has_valid_build_order(Physics::SRS) {
+        return ret;
+    }
+
+    // A pathological solution could make the search too big for the worker.
+    const MAX_FRONTIER: usize = 1 << 18;
+
     // TODO:  Return queues classified by physics.
-    let mut without_hold = board.supporting_queues(Physics::SRS);
-    without_hold.sort_unstable_by_key(|q| q.natural_order_key());
+    let (without_hold, truncated) =
+        board.supporting_queues_capped(Physics::SRS, Some(MAX_FRONTIER));
 
-    let with_hold = srs_4l::queue::Queue::unhold_many(&without_hold);
+    let with_hold = if include_with_hold {
+        srs_4l::queue::Queue::unhold_many(&without_hold)
+    } else {
+        Vec::new()
+    };
 
     solver::print(&board, &mut ret);
 
@@ -158,9 +634,241 @@ pub fn solution_info(encoded: &str) -> String {
         ret.pop();
     }
 
+    if truncated {
+        ret.push_str("|partial");
+    }
+
     ret
 }
 
+/// Reflect a solution left-to-right.
+///
+/// Returns the mirrored solution's encoding, then `|`, then its
+/// [`solution_info`] (with hold queues if `include_with_hold`).  The queues
+/// are searched again on the mirrored board rather than mirrored shape by
+/// shape, so the picture and the queues always agree.  Returns an empty string
+/// if the solution can't be decoded or built.
+#[wasm_bindgen]
+pub fn mirror_solution(encoded: &str, include_with_hold: bool) -> String {
+    let board = match base64_decode(encoded).and_then(|bits| BrokenBoard::decode(&bits)) {
+        Some(b) => b,
+        None => return String::new(),
+    };
+
+    let mut ret = String::new();
+    base64_encode(&board.mirror().encode(), &mut ret);
+
+    let info = solution_info(&ret, include_with_hold);
+    if info.is_empty() {
+        return String::new();
+    }
+
+    ret.push('|');
+    ret.push_str(&info);
+    ret
+}
+
+/// Encode a solution as a fumen which shows each piece being placed.
+///
+/// For each piece, there is a page with the piece at spawn, optionally a page
+/// with it dropping straight down from above its final column (if
+/// `soft_drop`), and a page with it in its final position, which locks it.
+/// Pieces are shown with fumen's piece overlay, so fumen clears lines as they
+/// fill.
+///
+/// The pieces are placed in an order that works with SRS.  Returns an empty
+/// string if the solution can't be decoded or built.
+#[wasm_bindgen]
+pub fn solution_fumen_animated(encoded: &str, soft_drop: bool) -> String {
+    use fumen::{CellColor, Fumen};
+
+    let board = match base64_decode(encoded).and_then(|bits| BrokenBoard::decode(&bits)) {
+        Some(b) => b,
+        None => return String::new(),
+    };
+
+    let order = match build_order(&board, Physics::SRS) {
+        Some(order) => order,
+        None => return String::new(),
+    };
+
+    let mut fumen = Fumen::default();
+    let first = fumen.add_page();
+    let garbage = board.garbage();
+    for idx in 0..40 {
+        if garbage.get(idx / 10, idx % 10) {
+            first.field[idx as usize / 10][idx as usize % 10] = CellColor::Grey;
+        }
+    }
+
+    let mut cleared = 0;
+    let mut progress = BrokenBoard::from_garbage(garbage.0);
+
+    for (i, &piece) in order.iter().enumerate() {
+        let spawn = Piece::spawn_centered(piece.shape, 10);
+        let mut frames = vec![fumen_piece(spawn, 0)];
+        if soft_drop {
+            let above = Piece { row: 4, ..piece };
+            frames.push(fumen_piece(above, 0));
+        }
+        frames.push(fumen_piece(piece, cleared));
+
+        let last = frames.len() - 1;
+        for (j, frame) in frames.into_iter().enumerate() {
+            let page = if i == 0 && j == 0 {
+                &mut fumen.pages[0]
+            } else {
+                fumen.add_page()
+            };
+            page.piece = Some(frame);
+            page.lock = j == last;
+        }
+
+        progress = progress.place(piece);
+        cleared = progress.cleared_rows.count_ones() as i8;
+    }
+
+    fumen.add_page();
+    fumen.encode()
+}
+
+/// Find an order to place the pieces of a solution in, so that each piece can
+/// be reached with `physics` when it's placed.
+///
+/// Each piece is in the coordinates of the board at the time it's placed,
+/// with cleared lines at the bottom.
+fn build_order(solution: &BrokenBoard, physics: Physics) -> Option<Vec<Piece>> {
+    fn search(
+        solution: &BrokenBoard,
+        board: BrokenBoard,
+        physics: Physics,
+        order: &mut Vec<Piece>,
+        dead: &mut HashSet<BrokenBoard>,
+    ) -> bool {
+        if order.len() == solution.pieces.len() {
+            return true;
+        }
+        if dead.contains(&board) {
+            return false;
+        }
+
+        for &broken in &solution.pieces {
+            let piece = match board.placeable(broken) {
+                Some(p) => p,
+                None => continue,
+            };
+            let canonical = Piece {
+                orientation: piece.orientation.canonical(piece.shape),
+                ..piece
+            };
+            if !Placements::place(board.board, piece.shape, physics)
+                .canonical()
+                .contains(canonical)
+            {
+                continue;
+            }
+
+            order.push(piece);
+            if search(solution, board.place(piece), physics, order, dead) {
+                return true;
+            }
+            order.pop();
+        }
+
+        dead.insert(board);
+        false
+    }
+
+    let mut order = Vec::new();
+    let start = BrokenBoard::from_garbage(solution.garbage().0);
+
+    search(solution, start, physics, &mut order, &mut HashSet::new()).then_some(order)
+}
+
+/// From the bottom-left corner of a piece's bounding box to its center of
+/// rotation in fumen, indexed by shape and orientation.
+const FUMEN_CENTERS: [[(i8, i8); 4]; 7] = [
+    [(1, 0), (0, 2), (2, 0), (0, 1)], // I
+    [(1, 0), (0, 1), (1, 1), (1, 1)], // J
+    [(1, 0), (0, 1), (1, 1), (1, 1)], // L
+    [(0, 0), (0, 1), (1, 1), (1, 0)], // O
+    [(1, 0), (0, 1), (1, 1), (1, 1)], // S
+    [(1, 0), (0, 1), (1, 1), (1, 1)], // T
+    [(1, 0), (0, 1), (1, 1), (1, 1)], // Z
+];
+
+/// Convert a piece to fumen's coordinates, where a piece is positioned by its
+/// center of rotation rather than the corner of its bounding box.
+///
+/// `cleared` lines are below the piece, which fumen has already removed.
+fn fumen_piece(piece: Piece, cleared: i8) -> fumen::Piece {
+    use fumen::{PieceType, RotationState};
+
+    let (dx, dy) = FUMEN_CENTERS[piece.shape as usize][piece.orientation as usize];
+
+    fumen::Piece {
+        kind: match piece.shape {
+            Shape::I => PieceType::I,
+            Shape::J => PieceType::J,
+            Shape::L => PieceType::L,
+            Shape::O => PieceType::O,
+            Shape::S => PieceType::S,
+            Shape::T => PieceType::T,
+            Shape::Z => PieceType::Z,
+        },
+        rotation: match piece.orientation {
+            Orientation::North => RotationState::North,
+            Orientation::East => RotationState::East,
+            Orientation::South => RotationState::South,
+            Orientation::West => RotationState::West,
+        },
+        x: (piece.col + dx) as u32,
+        y: (piece.row - cleared + dy) as u32,
+    }
+}
+
+/// Convert a piece from fumen's coordinates, undoing [`fumen_piece`].
+///
+/// Returns `None` if the piece is out of bounds to either side or below, or if
+/// its center is above the bottom four rows (so it certainly sticks out).
+fn from_fumen_piece(piece: fumen::Piece) -> Option<Piece> {
+    use fumen::{PieceType, RotationState};
+
+    let shape = match piece.kind {
+        PieceType::I => Shape::I,
+        PieceType::J => Shape::J,
+        PieceType::L => Shape::L,
+        PieceType::O => Shape::O,
+        PieceType::S => Shape::S,
+        PieceType::T => Shape::T,
+        PieceType::Z => Shape::Z,
+    };
+    let orientation = match piece.rotation {
+        RotationState::North => Orientation::North,
+        RotationState::East => Orientation::East,
+        RotationState::South => Orientation::South,
+        RotationState::West => Orientation::West,
+    };
+
+    if piece.x >= 10 || piece.y >= 4 {
+        return None;
+    }
+    let (dx, dy) = FUMEN_CENTERS[shape as usize][orientation as usize];
+
+    let piece = Piece {
+        shape,
+        col: piece.x as i8 - dx,
+        row: piece.y as i8 - dy,
+        orientation,
+    };
+    let (width, _height) = piece.bounding_box();
+    if piece.col < 0 || piece.row < 0 || piece.col + width as i8 > 10 {
+        return None;
+    }
+
+    Some(piece)
+}
+
 #[wasm_bindgen]
 pub fn decode_fumen(encoded: &str) -> String {
     #[derive(Default, Serialize)]
@@ -169,28 +877,321 @@ pub fn decode_fumen(encoded: &str) -> String {
         comment: Option<String>,
     }
 
-    fn inner(encoded: &str) -> Option<Decoded> {
-        use fumen::{CellColor, Fumen, Page};
+    let decoded = decode_first_page(encoded).map(|(field, comment)| Decoded {
+        field: field.0,
+        comment,
+    });
+
+    json::to_string(&decoded)
+}
+
+/// Decode many fumens at once, for loading a library of setups.
+///
+/// Each result is the field from the first page, along with the comment parsed
+/// as a queue (like `IJLOSTZ`) if it is one.  Fumens which cannot be decoded,
+/// or which have anything above the bottom four rows, produce `None`.
+pub fn import_fumens(fumens: &[&str]) -> Vec<Option<(Board, Option<srs_4l::queue::Queue>)>> {
+    fumens
+        .iter()
+        .map(|encoded| {
+            let (field, comment) = decode_first_page(encoded)?;
+            let queue = comment.as_deref().and_then(parse_queue);
+            Some((field, queue))
+        })
+        .collect()
+}
+
+/// Decode a fumen like [`decode_fumen`], but keep the color of each cell.
+///
+/// `cells` holds the bottom four rows, bottom row first, like the bits of a
+/// [`Board`].  Each cell is the index of the shape whose color it has (in the
+/// order of [`Shape::ALL`]), `7` for garbage, or `null` if it's empty.
+#[wasm_bindgen]
+pub fn decode_fumen_colored(encoded: &str) -> String {
+    #[derive(Default, Serialize)]
+    struct Decoded {
+        cells: Vec<Vec<Option<u8>>>,
+        comment: Option<String>,
+    }
+
+    let decoded = first_page(encoded).map(|page| Decoded {
+        cells: page.field[..4]
+            .iter()
+            .map(|row| row.iter().map(|&cell| color_index(cell)).collect())
+            .collect(),
+        comment: page.comment,
+    });
+
+    json::to_string(&decoded)
+}
+
+fn color_index(cell: fumen::CellColor) -> Option<u8> {
+    use fumen::CellColor;
+
+    let shape = match cell {
+        CellColor::Empty => return None,
+        CellColor::Grey => return Some(7),
+        CellColor::I => Shape::I,
+        CellColor::J => Shape::J,
+        CellColor::L => Shape::L,
+        CellColor::O => Shape::O,
+        CellColor::S => Shape::S,
+        CellColor::T => Shape::T,
+        CellColor::Z => Shape::Z,
+    };
+
+    Some(shape as u8)
+}
+
+/// Decode the first page of a fumen, if nothing is above the bottom four rows.
+///
+/// The page's piece, if it has one, is drawn over the field, so it's filled
+/// into the field too.  A piece which sticks out of the bottom four rows, or
+/// which overlaps the field, is rejected rather than cut off.
+fn first_page(encoded: &str) -> Option<fumen::Page> {
+    use fumen::{CellColor, Fumen};
+
+    let mut fumen = Fumen::decode(encoded).ok()?;
+    if fumen.pages.is_empty() {
+        return None;
+    }
+    let mut page = fumen.pages.swap_remove(0);
+
+    if page.field[4..] != [[CellColor::Empty; 10]; 19] || page.garbage_row != [CellColor::Empty; 10]
+    {
+        return None;
+    }
 
-        let fumen = Fumen::decode(encoded).ok()?;
-        let page: &Page = fumen.pages.get(0)?;
+    if let Some(piece) = page.piece.take() {
+        let piece = from_fumen_piece(piece)?;
+        let cells = piece.as_board_checked().ok()?;
+        let color = match piece.shape {
+            Shape::I => CellColor::I,
+            Shape::J => CellColor::J,
+            Shape::L => CellColor::L,
+            Shape::O => CellColor::O,
+            Shape::S => CellColor::S,
+            Shape::T => CellColor::T,
+            Shape::Z => CellColor::Z,
+        };
 
-        if page.field[4..] != [[CellColor::Empty; 10]; 19]
-            || page.garbage_row != [CellColor::Empty; 10]
-        {
-            return None;
+        for row in 0..4 {
+            for col in 0..10 {
+                if cells.get(row, col) {
+                    let cell = &mut page.field[row as usize][col as usize];
+                    if *cell != CellColor::Empty {
+                        return None;
+                    }
+                    *cell = color;
+                }
+            }
         }
+    }
+
+    Some(page)
+}
+
+fn decode_first_page(encoded: &str) -> Option<(Board, Option<String>)> {
+    use fumen::CellColor;
+
+    let page = first_page(encoded)?;
 
-        let mut field = 0;
-        for idx in 0..40 {
-            let cell: CellColor = page.field[idx / 10][idx % 10];
-            let filled = cell != CellColor::Empty;
-            field |= (filled as u64) << idx;
+    let mut field = 0;
+    for idx in 0..40 {
+        let cell: CellColor = page.field[idx / 10][idx % 10];
+        let filled = cell != CellColor::Empty;
+        field |= (filled as u64) << idx;
+    }
+
+    Some((Board(field), page.comment))
+}
+
+fn parse_queue(comment: &str) -> Option<srs_4l::queue::Queue> {
+    let shapes = comment
+        .trim()
+        .chars()
+        .map(parse_shape)
+        .collect::<Option<Vec<Shape>>>()?;
+
+    if shapes.is_empty() {
+        return None;
+    }
+
+    std::convert::TryFrom::try_from(shapes).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use smallvec::smallvec;
+    use srs_4l::{
+        base64::base64_encode,
+        brokenboard::{BrokenBoard, BrokenPiece},
+        gameplay::{Board, Orientation, Physics, Piece, Shape},
+    };
+
+    use super::{
+        check_fits, decode_first_page, fits_queue, solution_info, BitChoices, Queue, Solver,
+    };
+
+    fn encode(board: &BrokenBoard) -> String {
+        let mut encoded = String::new();
+        base64_encode(&board.encode(), &mut encoded);
+        encoded
+    }
+
+    fn o_at(row: i8) -> Piece {
+        Piece {
+            shape: Shape::O,
+            col: 0,
+            row,
+            orientation: Orientation::North,
         }
+    }
+
+    #[test]
+    fn solution_info_buildable() {
+        let board = BrokenBoard::empty().place(o_at(0)).place(o_at(2));
+        assert!(board.is_valid());
 
-        let comment = page.comment.clone();
-        Some(Decoded { field, comment })
+        let info = solution_info(&encode(&board), true);
+        let fields: Vec<&str> = info.split('|').collect();
+        assert_eq!(fields.len(), 3);
+        assert_eq!(fields[1], "OO");
+        assert_eq!(fields[2], "OO");
     }
 
-    json::to_string(&inner(encoded))
+    #[test]
+    fn solution_info_floating_piece() {
+        // Placing checks that the piece is grounded, so build the board by hand.
+        let board = BrokenBoard {
+            board: Board(0b0000000011_0000000011 << 20),
+            cleared_rows: 0,
+            pieces: smallvec![BrokenPiece {
+                low_mino: 20,
+                shape: Shape::O,
+                orientation: Orientation::North,
+                rows: 0b1100,
+            }],
+        };
+        assert!(board.is_valid());
+
+        assert_eq!(solution_info(&encode(&board), true), "");
+    }
+
+    fn fumen_with_piece(piece: fumen::Piece) -> String {
+        let mut fumen = fumen::Fumen::default();
+        fumen.add_page().piece = Some(piece);
+        fumen.encode()
+    }
+
+    #[test]
+    fn fumen_piece_is_imported() {
+        let encoded = fumen_with_piece(fumen::Piece {
+            kind: fumen::PieceType::T,
+            rotation: fumen::RotationState::North,
+            x: 4,
+            y: 0,
+        });
+
+        let (field, _) = decode_first_page(&encoded).unwrap();
+        assert_eq!(field, Board(0b0000010000_0000111000));
+    }
+
+    #[test]
+    fn fumen_piece_above_board() {
+        // Vertical, from row 1 through row 4.
+        let encoded = fumen_with_piece(fumen::Piece {
+            kind: fumen::PieceType::I,
+            rotation: fumen::RotationState::East,
+            x: 0,
+            y: 3,
+        });
+
+        assert_eq!(decode_first_page(&encoded), None);
+    }
+
+    #[test]
+    fn bit_choices_resume() {
+        let all: Vec<u64> = BitChoices::new(6, 3).collect();
+        assert_eq!(all.len(), 20);
+
+        let mut choices = BitChoices::new(6, 3);
+        for i in 0..all.len() {
+            let position = choices.position().unwrap();
+            let resumed: Vec<u64> = BitChoices::resume_from(6, 3, &position).collect();
+            assert_eq!(resumed, all[i..]);
+
+            choices.next();
+        }
+
+        assert_eq!(choices.position(), None);
+        assert_eq!(choices.next(), None);
+    }
+
+    #[test]
+    fn bit_choices_resume_none_set() {
+        let mut choices = BitChoices::new(5, 0);
+        assert_eq!(choices.position(), Some(vec![]));
+
+        let resumed: Vec<u64> = BitChoices::resume_from(5, 0, &[]).collect();
+        assert_eq!(resumed, [0]);
+
+        assert_eq!(choices.next(), Some(0));
+        assert_eq!(choices.position(), None);
+        assert_eq!(choices.next(), None);
+    }
+
+    #[test]
+    fn solvable_garbages_one_piece() {
+        let solver = Solver::from_boards(Vec::new());
+        let mut queue = Queue::new();
+        queue.add_shape('I');
+
+        let full = 0xFFFFF_FFFFF;
+        let solvable = solver.solvable_garbages(36, &queue, false, Physics::SRS);
+
+        assert!(solvable.contains(&Board(full & !(0b1111 << 30))));
+        assert!(solvable.contains(&Board(full & !0b1_0000000001_0000000001_0000000001)));
+        assert!(!solvable.contains(&Board(full & !0b11_0000000011)));
+        assert!(solvable.windows(2).all(|pair| pair[0] < pair[1]));
+        assert!(solver
+            .solvable_garbages(32, &queue, false, Physics::SRS)
+            .is_empty());
+    }
+
+    #[test]
+    fn queue_one_piece_too_long_without_hold() {
+        let solver = Solver::from_boards(Vec::new());
+        let mut queue = Queue::new();
+        queue.add_shape('I');
+        queue.add_shape('O');
+
+        // Only room for one I, in the top row.
+        let garbage = 0xFFFFF_FFFFF & !(0b1111 << 30);
+
+        assert!(check_fits(&queue, garbage, false).is_ok());
+        assert!(fits_queue(36, &queue, false));
+        assert_eq!(
+            solver.compute(&queue, garbage, false, Physics::SRS).len(),
+            1
+        );
+        assert!(solver
+            .solvable_garbages(36, &queue, false, Physics::SRS)
+            .contains(&Board(garbage)));
+
+        queue.add_shape('O');
+        assert!(check_fits(&queue, garbage, false).is_err());
+        assert!(!fits_queue(36, &queue, false));
+    }
+
+    #[test]
+    fn solve_reports_queue_too_long() {
+        let solver = Solver::from_boards(Vec::new());
+        let mut queue = Queue::new();
+        queue.add_bag("IJLOSTZ", 7);
+
+        let garbage = 0xFFFFF_FFFFF & !(0b1111 << 30);
+        let result = solver.solve(queue, garbage, true, "SRS".into());
+        assert!(result.starts_with("error: too many pieces"), "{}", result);
+    }
 }
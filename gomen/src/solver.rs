@@ -1,28 +1,110 @@
 //! Single-threaded solver that produces broken boards.
 
-use std::collections::{HashMap, HashSet};
+use std::{
+    collections::{HashMap, HashSet},
+    io::{self, Write},
+    ops::ControlFlow,
+};
 
 use smallvec::SmallVec;
 
 use srs_4l::{
     brokenboard::BrokenBoard,
-    gameplay::{Board, Physics, Shape},
+    gameplay::{Board, Physics, Piece, Shape},
+    queue::Queue,
     vector::Placements,
 };
 
 use crate::queue::{Bag, QueueState};
 
+/// Settings shared by every stage of the solver.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct Config {
+    pub can_hold: bool,
+    pub physics: Physics,
+}
+
+impl Config {
+    /// Placements which differ only by a symmetric orientation are merged,
+    /// since [`BrokenBoard`] only records the canonical orientation anyway.
+    /// See [`Placements::canonical`].
+    fn place(self, board: Board, shape: Shape) -> Placements {
+        Placements::place(board, shape, self.physics).canonical()
+    }
+}
+
+/// Per-shape masks which placed pieces must stay inside.
+struct Constraints {
+    masks: [Board; 7],
+}
+
+impl Constraints {
+    fn new(constraints: &[(Shape, Board)]) -> Constraints {
+        let mut masks = [Board(0xFFFFF_FFFFF); 7];
+
+        for &(shape, mask) in constraints {
+            masks[shape as usize].0 &= mask.0;
+        }
+
+        Constraints { masks }
+    }
+
+    /// Check a placement during [`scan`], where only the plain board is known.
+    ///
+    /// Once a line has been cleared, the rows of `piece` no longer match the
+    /// rows of the original field, so the placement is allowed and left for
+    /// [`place`] to check.
+    fn allows_scan(&self, old_board: Board, piece: Piece) -> bool {
+        let cleared_any = old_board.0 & 0b1111111111 == 0b1111111111;
+        cleared_any || self.masks[piece.shape as usize].contains_all(piece.as_board())
+    }
+
+    /// Check a placement exactly, in the coordinates of the original field.
+    fn allows(&self, old_board: &BrokenBoard, new_board: &BrokenBoard, shape: Shape) -> bool {
+        let mask = self.masks[shape as usize];
+        if mask == Board(0xFFFFF_FFFFF) {
+            return true;
+        }
+
+        let minoes = new_board.to_broken_bitboard().0 & !old_board.to_broken_bitboard().0;
+        mask.contains_all(Board(minoes))
+    }
+}
+
+/// Count the pieces in the queue.
+fn count_pieces(bags: &[Bag]) -> usize {
+    bags.iter().map(|b| b.count as usize).sum()
+}
+
+/// Decide whether to place the last piece (from hold):  only if there's room
+/// for it.
+fn room_for_last(start: Board, bags: &[Bag]) -> bool {
+    let new_mino_count = count_pieces(bags) as u32 * 4;
+    start.0.count_ones() + new_mino_count <= 40
+}
+
+/// How many times to report progress during each stage, at most.
+const PROGRESS_STEPS: usize = 64;
+
+/// Report progress every this many boards, for a stage with `total` boards.
+///
+/// Small stages still report as they go, and huge stages don't flood the web
+/// worker with messages.
+fn progress_interval(total: usize) -> usize {
+    (total / PROGRESS_STEPS).max(1)
+}
+
 type ScanStage = HashMap<Board, (SmallVec<[QueueState; 7]>, SmallVec<[Board; 6]>)>;
 
 fn scan(
     legal_boards: &HashSet<Board>,
     start: Board,
     bags: &[Bag],
-    piece_count: usize,
-    can_hold: bool,
+    config: Config,
+    constraints: &Constraints,
     place_last: bool,
-    physics: Physics,
 ) -> Vec<ScanStage> {
+    let piece_count = count_pieces(bags);
     let mut stages = Vec::new();
 
     let mut prev: ScanStage = HashMap::new();
@@ -37,22 +119,25 @@ fn scan(
         let mut next: ScanStage = HashMap::new();
 
         for (board_idx, (&old_board, (old_queues, _preds))) in prev.iter().enumerate() {
-            if board_idx % 4096 == 0 {
+            if board_idx % progress_interval(prev.len()) == 0 {
                 crate::progress(piece_count, stage, board_idx, prev.len());
             }
 
             for shape in Shape::ALL {
                 let is_first = i == 0;
-                let new_queues = bag.take(old_queues, shape, is_first, can_hold);
+                let new_queues = bag.take(old_queues, shape, is_first, config.can_hold);
 
                 if new_queues.is_empty() {
                     continue;
                 }
 
-                for (_, new_board) in Placements::place(old_board, shape, physics).canonical() {
+                for (piece, new_board) in config.place(old_board, shape) {
                     if !legal_boards.is_empty() && !legal_boards.contains(&new_board) {
                         continue;
                     }
+                    if !constraints.allows_scan(old_board, piece) {
+                        continue;
+                    }
 
                     let (queues, preds) = next.entry(new_board).or_default();
                     if !preds.contains(&old_board) {
@@ -75,16 +160,19 @@ fn scan(
         let mut next: ScanStage = HashMap::new();
 
         for (board_idx, (&old_board, (old_queues, _preds))) in prev.iter().enumerate() {
-            if board_idx % 4096 == 0 {
+            if board_idx % progress_interval(prev.len()) == 0 {
                 crate::progress(piece_count, piece_count, board_idx, prev.len());
             }
 
             for shape in Shape::ALL {
                 if old_queues.iter().any(|queue| queue.hold() == Some(shape)) {
-                    for (_, new_board) in Placements::place(old_board, shape, physics).canonical() {
+                    for (piece, new_board) in config.place(old_board, shape) {
                         if !legal_boards.is_empty() && !legal_boards.contains(&new_board) {
                             continue;
                         }
+                        if !constraints.allows_scan(old_board, piece) {
+                            continue;
+                        }
 
                         let (_queues, preds) = next.entry(new_board).or_default();
                         if !preds.contains(&old_board) {
@@ -105,6 +193,71 @@ fn scan(
     stages
 }
 
+/// Write the graph found by [`scan`] in [GraphViz DOT](https://graphviz.org/)
+/// format.
+///
+/// Each node is a board, drawn as its four rows, and each edge goes from a
+/// board to one that can follow it by placing one piece.  Nodes are grouped in
+/// ranks by stage.
+fn export_graph_dot(stages: &[ScanStage], w: &mut impl Write) -> io::Result<()> {
+    writeln!(w, "digraph scan {{")?;
+    writeln!(w, "  node [shape=box, fontname=monospace];")?;
+
+    for stage in stages {
+        write!(w, "  {{ rank=same;")?;
+        for board in stage.keys() {
+            write!(w, " {};", board.0)?;
+        }
+        writeln!(w, " }}")?;
+
+        for (&board, (_queues, preds)) in stage {
+            writeln!(
+                w,
+                "  {} [label=\"{}\"];",
+                board.0,
+                board.to_display_rows().join("\\n")
+            )?;
+
+            for pred in preds {
+                writeln!(w, "  {} -> {};", pred.0, board.0)?;
+            }
+        }
+    }
+
+    writeln!(w, "}}")
+}
+
+/// Run the first stage of the solver, and write every board it visits as a
+/// graph in [GraphViz DOT](https://graphviz.org/) format.
+///
+/// This is meant for looking at small searches.  Large searches make graphs
+/// much too big to draw.
+pub fn scan_dot(
+    legal_boards: &HashSet<Board>,
+    start: Board,
+    bags: &[Bag],
+    config: Config,
+    w: &mut impl Write,
+) -> io::Result<()> {
+    let stages = if bags.is_empty() {
+        let mut only: ScanStage = HashMap::new();
+        only.insert(start, Default::default());
+        vec![only]
+    } else {
+        let place_last = room_for_last(start, bags);
+        scan(
+            legal_boards,
+            start,
+            bags,
+            config,
+            &Constraints::new(&[]),
+            place_last,
+        )
+    };
+
+    export_graph_dot(&stages, w)
+}
+
 fn cull(scanned: &[ScanStage]) -> HashSet<Board> {
     let mut culled = HashSet::new();
     let mut iter = scanned.iter().rev();
@@ -127,17 +280,26 @@ fn cull(scanned: &[ScanStage]) -> HashSet<Board> {
     culled
 }
 
+/// Boards in [`place`], along with how they can be continued, and the least
+/// order (by [`Queue::natural_order_key`]) in which their pieces were placed.
+type PlaceStage = HashMap<BrokenBoard, (SmallVec<[QueueState; 7]>, Queue)>;
+
+/// Place pieces for real, following only boards that survived [`cull`].
+///
+/// `sink` is called once for each solution, as soon as it's found.  If it
+/// breaks, the search stops right away and the stage returned is incomplete.
 fn place(
     culled: &HashSet<Board>,
     start: BrokenBoard,
     bags: &[Bag],
-    piece_count: usize,
-    can_hold: bool,
+    config: Config,
+    constraints: &Constraints,
     place_last: bool,
-    physics: Physics,
-) -> HashMap<BrokenBoard, SmallVec<[QueueState; 7]>> {
+    sink: &mut dyn FnMut(&BrokenBoard) -> ControlFlow<()>,
+) -> PlaceStage {
+    let piece_count = count_pieces(bags);
     let mut prev = HashMap::new();
-    prev.insert(start, bags.first().unwrap().init_hold());
+    prev.insert(start, (bags.first().unwrap().init_hold(), Queue::empty()));
 
     for (stage, (bag, i)) in bags
         .iter()
@@ -145,26 +307,40 @@ fn place(
         .skip(1)
         .enumerate()
     {
-        let mut next: HashMap<BrokenBoard, SmallVec<[QueueState; 7]>> = HashMap::new();
+        let mut next: PlaceStage = HashMap::new();
+        let is_final = !place_last && stage + 2 == piece_count;
 
-        for (board_idx, (old_board, old_queues)) in prev.iter().enumerate() {
-            if board_idx % 4096 == 0 {
+        for (board_idx, (old_board, (old_queues, old_order))) in prev.iter().enumerate() {
+            if board_idx % progress_interval(prev.len()) == 0 {
                 crate::progress(piece_count, piece_count + 1 + stage, board_idx, prev.len());
             }
 
             for shape in Shape::ALL {
                 let is_first = i == 0;
-                let new_queues = bag.take(old_queues, shape, is_first, can_hold);
+                let new_queues = bag.take(old_queues, shape, is_first, config.can_hold);
 
                 if new_queues.is_empty() {
                     continue;
                 }
 
-                for (piece, new_board) in
-                    Placements::place(old_board.board, shape, physics).canonical()
-                {
+                for (piece, new_board) in config.place(old_board.board, shape) {
                     if culled.contains(&new_board) {
-                        let queues = next.entry(old_board.place(piece)).or_default();
+                        let new_board = old_board.place(piece);
+                        if !constraints.allows(old_board, &new_board, shape) {
+                            continue;
+                        }
+
+                        if is_final && !next.contains_key(&new_board) && sink(&new_board).is_break()
+                        {
+                            return next;
+                        }
+
+                        let order = old_order.push_last(shape);
+                        let (queues, least) =
+                            next.entry(new_board).or_insert((SmallVec::new(), order));
+                        if order.natural_order_key() < least.natural_order_key() {
+                            *least = order;
+                        }
                         for &queue in &new_queues {
                             if !queues.contains(&queue) {
                                 queues.push(queue);
@@ -179,20 +355,36 @@ fn place(
     }
 
     if place_last {
-        let mut next: HashMap<BrokenBoard, SmallVec<[QueueState; 7]>> = HashMap::new();
+        let mut next: PlaceStage = HashMap::new();
 
-        for (board_idx, (old_board, old_queues)) in prev.iter().enumerate() {
-            if board_idx % 4096 == 0 {
+        for (board_idx, (old_board, (old_queues, old_order))) in prev.iter().enumerate() {
+            if board_idx % progress_interval(prev.len()) == 0 {
                 crate::progress(piece_count, 2 * piece_count + 1, board_idx, prev.len());
             }
 
             for shape in Shape::ALL {
                 if old_queues.iter().any(|queue| queue.hold() == Some(shape)) {
-                    for (piece, new_board) in
-                        Placements::place(old_board.board, shape, physics).canonical()
-                    {
+                    for (piece, new_board) in config.place(old_board.board, shape) {
                         if culled.contains(&new_board) {
-                            next.insert(old_board.place(piece), SmallVec::new());
+                            let new_board = old_board.place(piece);
+                            if !constraints.allows(old_board, &new_board, shape) {
+                                continue;
+                            }
+
+                            let order = old_order.push_last(shape);
+                            match next.get_mut(&new_board) {
+                                Some((_, least)) => {
+                                    if order.natural_order_key() < least.natural_order_key() {
+                                        *least = order;
+                                    }
+                                }
+                                None => {
+                                    if sink(&new_board).is_break() {
+                                        return next;
+                                    }
+                                    next.insert(new_board, (SmallVec::new(), order));
+                                }
+                            }
                         }
                     }
                 }
@@ -200,6 +392,13 @@ fn place(
         }
 
         prev = next;
+    } else if piece_count == 1 {
+        // The only piece stays in hold, so the start is the solution.
+        for board in prev.keys() {
+            if sink(board).is_break() {
+                break;
+            }
+        }
     }
 
     crate::progress(piece_count, 2 * piece_count + 1, 1, 1);
@@ -207,48 +406,447 @@ fn place(
     prev
 }
 
+/// Find every perfect clear solution.
+///
+/// Every board the search passes through must be in `legal_boards`.  If it is
+/// empty, every board is allowed.
+///
+/// `initial_hold` is the shape already in hold before the queue starts, if
+/// any.  It's only used if `config.can_hold`.
+///
+/// Each entry in `constraints` limits where pieces of one shape may be placed:
+/// all of their minoes must fall within the mask, in the coordinates of the
+/// starting field.  Shapes without an entry may go anywhere.
 pub fn compute(
     legal_boards: &HashSet<Board>,
     start: &BrokenBoard,
     bags: &[Bag],
-    can_hold: bool,
-    physics: Physics,
+    initial_hold: Option<Shape>,
+    config: Config,
+    constraints: &[(Shape, Board)],
 ) -> Vec<BrokenBoard> {
+    let bags = &with_initial_hold(bags, initial_hold, config);
     if bags.is_empty() {
         return vec![start.clone()];
     }
 
-    let piece_count = bags.iter().map(|b| b.count as usize).sum();
-    let new_mino_count = piece_count as u32 * 4;
-    let place_last = start.board.0.count_ones() + new_mino_count <= 40;
+    let constraints = Constraints::new(constraints);
 
+    let place_last = room_for_last(start.board, bags);
     let scanned = scan(
         legal_boards,
         start.board,
         bags,
-        piece_count,
-        can_hold,
+        config,
+        &constraints,
         place_last,
-        physics,
     );
     let culled = cull(&scanned);
     let mut placed = place(
         &culled,
         start.clone(),
         bags,
-        piece_count,
-        can_hold,
+        config,
+        &constraints,
         place_last,
-        physics,
+        &mut |_| ControlFlow::Continue(()),
     );
 
-    let mut solutions: Vec<BrokenBoard> =
-        placed.drain().map(|(board, _queue_states)| board).collect();
+    let mut solutions: Vec<BrokenBoard> = placed.drain().map(|(board, _)| board).collect();
     solutions.sort_unstable();
 
     solutions
 }
 
+/// Put a piece in hold before the queue starts.
+///
+/// With hold, holding a piece is the same as having it at the front of the
+/// queue:  either way, the first piece placed can be it or the next one, and
+/// the other is left in hold.  So the held piece becomes a bag of its own.
+/// Without hold, the held piece can never come out, so it's dropped.
+pub fn with_initial_hold(bags: &[Bag], initial_hold: Option<Shape>, config: Config) -> Vec<Bag> {
+    let mut with_hold = Vec::with_capacity(bags.len() + 1);
+
+    if let (Some(shape), true) = (initial_hold, config.can_hold) {
+        with_hold.push(Bag::new(&[shape], 1));
+    }
+    with_hold.extend_from_slice(bags);
+
+    with_hold
+}
+
+/// Find every perfect clear solution, like [`compute`], along with the least
+/// queue that produces it.
+///
+/// Each queue is the order in which the solution's pieces are placed (not the
+/// order they're drawn, if hold is used), and it's the least such order by
+/// [`Queue::natural_order_key`] that the bags allow.  Solutions are sorted by
+/// their queues, so that solutions for similar queues are close together.
+pub fn compute_by_queue(
+    legal_boards: &HashSet<Board>,
+    start: &BrokenBoard,
+    bags: &[Bag],
+    initial_hold: Option<Shape>,
+    config: Config,
+    constraints: &[(Shape, Board)],
+) -> Vec<(BrokenBoard, Queue)> {
+    let bags = &with_initial_hold(bags, initial_hold, config);
+    if bags.is_empty() {
+        return vec![(start.clone(), Queue::empty())];
+    }
+
+    let constraints = Constraints::new(constraints);
+
+    let place_last = room_for_last(start.board, bags);
+    let scanned = scan(
+        legal_boards,
+        start.board,
+        bags,
+        config,
+        &constraints,
+        place_last,
+    );
+    let culled = cull(&scanned);
+    let mut placed = place(
+        &culled,
+        start.clone(),
+        bags,
+        config,
+        &constraints,
+        place_last,
+        &mut |_| ControlFlow::Continue(()),
+    );
+
+    let mut solutions: Vec<(BrokenBoard, Queue)> = placed
+        .drain()
+        .map(|(board, (_queue_states, order))| (board, order))
+        .collect();
+    solutions.sort_unstable_by(|(board_a, order_a), (board_b, order_b)| {
+        (order_a.natural_order_key(), board_a).cmp(&(order_b.natural_order_key(), board_b))
+    });
+
+    solutions
+}
+
+/// Find every perfect clear solution, like [`compute`], but send each one to
+/// `sink` as soon as it's found instead of collecting them.
+///
+/// This lets a command-line tool print solutions while the search is still
+/// running.  Solutions arrive in no particular order.  If the receiver hangs
+/// up, the search still finishes, but nothing more is sent.
+#[cfg(not(target_arch = "wasm32"))]
+pub fn compute_streaming(
+    legal_boards: &HashSet<Board>,
+    start: &BrokenBoard,
+    bags: &[Bag],
+    config: Config,
+    constraints: &[(Shape, Board)],
+    sink: crossbeam::channel::Sender<BrokenBoard>,
+) {
+    if bags.is_empty() {
+        let _ = sink.send(start.clone());
+        return;
+    }
+
+    let constraints = Constraints::new(constraints);
+
+    let place_last = room_for_last(start.board, bags);
+    let scanned = scan(
+        legal_boards,
+        start.board,
+        bags,
+        config,
+        &constraints,
+        place_last,
+    );
+    let culled = cull(&scanned);
+    place(
+        &culled,
+        start.clone(),
+        bags,
+        config,
+        &constraints,
+        place_last,
+        &mut |board| {
+            let _ = sink.send(board.clone());
+            ControlFlow::Continue(())
+        },
+    );
+}
+
+/// Count the solutions [`compute`] would find, but stop counting at `cap`.
+///
+/// This is much cheaper than finding every solution when only a few matter:
+/// with a `cap` of 2, the result tells apart no solution, a unique solution,
+/// and several solutions.  The search stops as soon as the cap is reached.
+pub fn solution_count_capped(
+    legal_boards: &HashSet<Board>,
+    start: &BrokenBoard,
+    bags: &[Bag],
+    config: Config,
+    constraints: &[(Shape, Board)],
+    cap: usize,
+) -> usize {
+    if cap == 0 {
+        return 0;
+    }
+    if bags.is_empty() {
+        return 1;
+    }
+
+    let constraints = Constraints::new(constraints);
+
+    let place_last = room_for_last(start.board, bags);
+    let scanned = scan(
+        legal_boards,
+        start.board,
+        bags,
+        config,
+        &constraints,
+        place_last,
+    );
+    let culled = cull(&scanned);
+
+    // Placement already skips repeated boards, so each call is a new solution.
+    let mut count = 0;
+
+    place(
+        &culled,
+        start.clone(),
+        bags,
+        config,
+        &constraints,
+        place_last,
+        &mut |_| {
+            count += 1;
+
+            if count >= cap {
+                ControlFlow::Break(())
+            } else {
+                ControlFlow::Continue(())
+            }
+        },
+    );
+
+    count
+}
+
+/// Find every perfect clear solution, like [`compute`], along with how many
+/// input queues produce it.
+///
+/// A queue is counted if the bags can produce it, and if the solution can be
+/// built from it (using hold if `config.can_hold`).  Dividing by the total
+/// number of queues gives the probability that the solution is possible.
+///
+/// Requires that the bags contain at most 10 pieces in total, since longer
+/// queues cannot be counted.
+pub fn compute_weighted(
+    legal_boards: &HashSet<Board>,
+    start: &BrokenBoard,
+    bags: &[Bag],
+    config: Config,
+    constraints: &[(Shape, Board)],
+) -> Vec<(BrokenBoard, usize)> {
+    let piece_count: usize = bags.iter().map(|b| b.count as usize).sum();
+    assert!(piece_count <= 10);
+
+    compute(legal_boards, start, bags, None, config, constraints)
+        .into_iter()
+        .map(|board| {
+            let placed_count = board.pieces.len() - start.pieces.len();
+
+            let mut queues = board.supporting_queues(config.physics);
+            if placed_count < piece_count {
+                // The last piece stayed in hold, so it could have been anything.
+                queues = queues
+                    .iter()
+                    .flat_map(|&queue| Shape::ALL.map(|shape| queue.push_last(shape)))
+                    .collect();
+            }
+            if config.can_hold {
+                queues = Queue::unhold_many(&queues);
+            }
+
+            let weight = queues
+                .into_iter()
+                .filter(|&queue| bags_produce(bags, queue))
+                .count();
+
+            (board, weight)
+        })
+        .collect()
+}
+
+/// Find the perfect clear solutions which place the fewest pieces.
+///
+/// The solver is run placing more and more pieces, and the first count with
+/// any perfect clear wins.  This finds clears lower than four lines, which
+/// [`compute`] only returns if the queue happens to be the right length.
+/// Returns nothing if even the whole queue can't perfect clear.
+///
+/// With hold, a clear placing `n` pieces can take them from the first `n + 1`
+/// pieces of the queue, leaving the last one drawn in hold.  So both the first
+/// `n` pieces, all placed, and the first `n + 1`, with one left in hold, are
+/// searched.
+pub fn compute_minimal(
+    legal_boards: &HashSet<Board>,
+    start: &BrokenBoard,
+    bags: &[Bag],
+    config: Config,
+    constraints: &[(Shape, Board)],
+) -> Vec<BrokenBoard> {
+    let piece_count = count_pieces(bags);
+    let constraints = Constraints::new(constraints);
+
+    // Perfect clears drawing the first `len` pieces of the queue.
+    let solve = |len: usize, hold_last: bool| -> Vec<BrokenBoard> {
+        let prefix = bags_prefix(bags, len);
+        let place_last = !hold_last && room_for_last(start.board, &prefix);
+
+        let scanned = scan(
+            legal_boards,
+            start.board,
+            &prefix,
+            config,
+            &constraints,
+            place_last,
+        );
+        let culled = cull(&scanned);
+        place(
+            &culled,
+            start.clone(),
+            &prefix,
+            config,
+            &constraints,
+            place_last,
+            &mut |_| ControlFlow::Continue(()),
+        )
+        .into_keys()
+        .filter(|solution| is_perfect_clear(solution.board))
+        .collect()
+    };
+
+    for placed in 1..=piece_count {
+        let mut solutions = solve(placed, false);
+        if config.can_hold && placed < piece_count {
+            solutions.extend(solve(placed + 1, true));
+        }
+
+        if !solutions.is_empty() {
+            solutions.sort_unstable();
+            solutions.dedup();
+            return solutions;
+        }
+    }
+
+    Vec::new()
+}
+
+/// Find every solution which clears down to exactly `residual`, rather than to
+/// an empty board.
+///
+/// `residual` is in the coordinates of a fresh field:  whatever is left after
+/// the cleared lines are removed, shifted down to the bottom.  So it can be
+/// used as the garbage for solving the next bag, and a residual of
+/// `Board::empty()` means a perfect clear.
+///
+/// As in [`compute`], every piece is placed, except possibly the last one,
+/// which may stay in hold.
+pub fn compute_to_residual(
+    legal_boards: &HashSet<Board>,
+    start: &BrokenBoard,
+    bags: &[Bag],
+    residual: Board,
+    config: Config,
+    constraints: &[(Shape, Board)],
+) -> Vec<BrokenBoard> {
+    let mut solutions = compute(legal_boards, start, bags, None, config, constraints);
+    solutions.retain(|solution| residual_of(solution.board) == residual);
+    solutions
+}
+
+/// What's left of a board once its cleared lines are removed.
+///
+/// Cleared lines are kept at the bottom of the board, so they're shifted out.
+fn residual_of(board: Board) -> Board {
+    let mut rest = board.0;
+    while rest & 0b1111111111 == 0b1111111111 {
+        rest >>= 10;
+    }
+    Board(rest)
+}
+
+/// The bags drawing only the first `len` pieces of `bags`.
+fn bags_prefix(bags: &[Bag], mut len: usize) -> Vec<Bag> {
+    let mut prefix = Vec::new();
+
+    for bag in bags {
+        if len == 0 {
+            break;
+        }
+
+        let count = len.min(bag.count as usize) as u8;
+        prefix.push(Bag {
+            count,
+            ..bag.clone()
+        });
+        len -= count as usize;
+    }
+
+    prefix
+}
+
+/// Check whether every line with anything in it has been cleared.
+///
+/// Cleared lines are kept at the bottom of the board, so this means the board
+/// is some number of full lines and nothing else.
+fn is_perfect_clear(board: Board) -> bool {
+    let lines = board.0.count_ones() / 10;
+    lines != 0 && board.0 == (1 << (lines * 10)) - 1
+}
+
+/// Check whether drawing from `bags` in order could produce exactly `queue`.
+fn bags_produce(bags: &[Bag], mut queue: Queue) -> bool {
+    for bag in bags {
+        let mut state = QueueState(bag.full);
+
+        for _ in 0..bag.count {
+            let taken = queue.next().and_then(|shape| state.take(bag, shape));
+
+            match taken {
+                Some(new) => state = new,
+                None => return false,
+            }
+        }
+    }
+
+    queue.is_empty()
+}
+
+/// Find every board reachable from `start` by placing exactly the pieces in
+/// `bags`, in order (with hold if `config.can_hold`).
+///
+/// This is the search done by [`compute`], without any legal-board filter and
+/// without requiring the end result to be a perfect clear.  If there is still a
+/// piece in hold at the end and there is room on the board, it is placed too.
+pub fn reachable(start: Board, bags: &[Bag], config: Config) -> HashSet<Board> {
+    if bags.is_empty() {
+        return HashSet::from([start]);
+    }
+
+    let place_last = room_for_last(start, bags);
+    let mut scanned = scan(
+        &HashSet::new(),
+        start,
+        bags,
+        config,
+        &Constraints::new(&[]),
+        place_last,
+    );
+
+    scanned.pop().unwrap().into_keys().collect()
+}
+
 pub fn print(board: &BrokenBoard, to: &mut String) {
     let pieces: Vec<(Shape, Board)> = board
         .pieces
@@ -274,3 +872,132 @@ pub fn print(board: &BrokenBoard, to: &mut String) {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use srs_4l::{
+        brokenboard::BrokenBoard,
+        gameplay::{Physics, Shape},
+    };
+
+    use super::*;
+
+    /// Bags which draw exactly these shapes, in order.
+    fn fixed_queue(shapes: &[Shape]) -> Vec<Bag> {
+        shapes.iter().map(|&shape| Bag::new(&[shape], 1)).collect()
+    }
+
+    fn config(can_hold: bool) -> Config {
+        Config {
+            can_hold,
+            physics: Physics::SRS,
+        }
+    }
+
+    #[test]
+    fn minimal_leaves_last_piece_in_hold() {
+        use Shape::*;
+
+        let bags = fixed_queue(&[O, O, O, O, T, O]);
+        let start = BrokenBoard::empty();
+
+        let solutions = compute_minimal(&HashSet::new(), &start, &bags, config(true), &[]);
+        assert!(!solutions.is_empty());
+        for solution in &solutions {
+            assert_eq!(solution.board, Board(0xFFFFF));
+            assert_eq!(solution.pieces.len(), 5);
+            assert!(solution.pieces.iter().all(|piece| piece.shape == O));
+        }
+
+        assert!(compute_minimal(&HashSet::new(), &start, &bags, config(false), &[]).is_empty());
+    }
+
+    #[test]
+    fn initial_hold_is_front_of_queue() {
+        use Shape::*;
+
+        // Everything but the three columns on the left.
+        let start = BrokenBoard::from_garbage(0b1111111000_1111111000_1111111000_1111111000);
+
+        let held = compute(
+            &HashSet::new(),
+            &start,
+            &fixed_queue(&[L, J]),
+            Some(T),
+            config(true),
+            &[],
+        );
+        let first = compute(
+            &HashSet::new(),
+            &start,
+            &fixed_queue(&[T, L, J]),
+            None,
+            config(true),
+            &[],
+        );
+
+        assert!(!held.is_empty());
+        assert_eq!(held, first);
+
+        // Without hold, the held piece can never come out.
+        let bags = fixed_queue(&[L, J]);
+        assert_eq!(with_initial_hold(&bags, Some(T), config(false)), bags);
+    }
+
+    #[test]
+    fn residual() {
+        assert_eq!(residual_of(Board(0)), Board(0));
+        assert_eq!(residual_of(Board(0b11_1111111111)), Board(0b11));
+        assert_eq!(
+            residual_of(Board(0b1111111111_0000000011)),
+            Board(0b1111111111_0000000011)
+        );
+
+        let start = BrokenBoard::from_garbage(0b1111110000);
+        let bags = fixed_queue(&[Shape::I]);
+        let to_residual = |residual| {
+            compute_to_residual(&HashSet::new(), &start, &bags, residual, config(false), &[])
+        };
+
+        let cleared = to_residual(Board(0));
+        assert_eq!(cleared.len(), 1);
+        assert_eq!(cleared[0].board, Board(0b1111111111));
+
+        let on_top = to_residual(Board(0b0011110000_1111110000));
+        assert_eq!(on_top.len(), 1);
+
+        assert!(to_residual(Board(0b1111_1111110000)).is_empty());
+    }
+
+    #[test]
+    fn capped_count() {
+        let legal = HashSet::new();
+        let count = |garbage, shape, cap| {
+            let start = BrokenBoard::from_garbage(garbage);
+            let bags = fixed_queue(&[shape]);
+            solution_count_capped(&legal, &start, &bags, config(false), &[], cap)
+        };
+
+        // Only the top row has room, so an I fits in exactly one place.
+        let one_slot = 0b1111110000_1111111111_1111111111_1111111111;
+        assert_eq!(count(one_slot, Shape::O, 2), 0);
+        assert_eq!(count(one_slot, Shape::I, 2), 1);
+        assert_eq!(count(one_slot, Shape::I, 0), 0);
+
+        let open = 0b1111110000;
+        let all = compute(
+            &legal,
+            &BrokenBoard::from_garbage(open),
+            &fixed_queue(&[Shape::I]),
+            None,
+            config(false),
+            &[],
+        );
+        assert!(all.len() > 2);
+
+        assert_eq!(count(open, Shape::I, 1), 1);
+        assert_eq!(count(open, Shape::I, 2), 2);
+        assert_eq!(count(open, Shape::I, all.len()), all.len());
+        assert_eq!(count(open, Shape::I, 1000), all.len());
+    }
+}
@@ -47,9 +47,14 @@ async function main() {
             }
         }
 
-        let solutions =
-            solver.solve(queue, query.garbage, query.hold, query.physics)
-                  .split(",");
+        let result = solver.solve(queue, query.garbage, query.hold, query.physics);
+
+        if (result.startsWith("error: ")) {
+            postMessage({ kind: "ok", query, solutions: [], error: result.slice(7) });
+            return;
+        }
+
+        let solutions = result.split(",");
 
         if (solutions[0] == "") {
             solutions = [];
@@ -1,4 +1,4 @@
-use std::{io::Write, time::Duration};
+use std::{collections::hash_map::Entry, io::Write, time::Duration};
 
 use rayon::{
     iter::{IntoParallelRefMutIterator, ParallelIterator},
@@ -16,12 +16,33 @@ type NoHashBuilder = nohash::BuildNoHashHasher<u64>;
 type Map = ShardedHashMap<Board, SmallVec<[Board; 6]>, 20, NoHashBuilder>;
 type Set = ShardedHashMap<Board, (), 20, NoHashBuilder>;
 
+/// The physics whose placements, unioned together, give every placement valid
+/// in any of `targets`.
+///
+/// SRS is left out if another target is a superset of it.
+fn minimal_physics(targets: &[Physics]) -> Vec<Physics> {
+    let covers_srs = targets
+        .iter()
+        .any(|&p| p != Physics::SRS && p.is_srs_superset());
+
+    targets
+        .iter()
+        .copied()
+        .filter(|&p| !(p == Physics::SRS && covers_srs))
+        .collect()
+}
+
 pub fn compute() -> Vec<Board> {
     let mut stages: Vec<Map> = Vec::new();
     stages.resize_with(11, Map::new);
 
     stages[0].insert(Board::empty(), SmallVec::new());
 
+    let physics = minimal_physics(&Physics::ALL);
+
+    // Counted as boards are inserted, to save a pass over every shard.
+    let mut total = 1;
+
     for iter in 1..=10 {
         let (prev_stage, this_stage) = match &mut stages[iter - 1..] {
             [prev, this, ..] => (prev, this),
@@ -29,7 +50,7 @@ pub fn compute() -> Vec<Board> {
         };
 
         let counter = Counter::zero();
-        let total = prev_stage.len();
+        let inserted = Counter::zero();
 
         crossbeam::scope(|s| {
             s.spawn(|_| loop {
@@ -49,18 +70,25 @@ pub fn compute() -> Vec<Board> {
 
             prev_stage.par_iter_mut().for_each(|(&board, _preds)| {
                 for shape in Shape::ALL {
-                    // No need to use Physics::SRS, since Jstris placements are
-                    // a superset of SRS placements.
-                    for (_piece, new_board) in (Placements::place(board, shape, Physics::Jstris)
-                        | Placements::place(board, shape, Physics::Tetrio))
-                    .canonical()
-                    {
+                    let placements = physics
+                        .iter()
+                        .map(|&p| Placements::place(board, shape, p))
+                        .reduce(|a, b| a | b)
+                        .unwrap();
+
+                    for (_piece, new_board) in placements.canonical() {
                         if new_board.has_isolated_cell() || new_board.has_imbalanced_split() {
                             continue;
                         }
 
                         let mut guard = this_stage.get_shard_guard(&new_board);
-                        let preds = guard.entry(new_board).or_default();
+                        let preds = match guard.entry(new_board) {
+                            Entry::Occupied(entry) => entry.into_mut(),
+                            Entry::Vacant(entry) => {
+                                inserted.increment();
+                                entry.insert(SmallVec::new())
+                            }
+                        };
                         if !preds.contains(&board) {
                             preds.push(board);
                         }
@@ -72,6 +100,10 @@ pub fn compute() -> Vec<Board> {
         .unwrap();
 
         eprintln!();
+
+        // Every thread has been joined, so this is the final count.
+        total = inserted.get() as usize;
+        println!("{:>4}-piece boards reached: {:>9}", iter, total);
     }
 
     let stages: Vec<_> = stages.drain(..).map(ShardedHashMap::freeze).collect();
@@ -1,5 +1,7 @@
 use std::io::{self, Read, Write};
 
+use rayon::prelude::*;
+
 use crate::gameplay::Board;
 
 pub fn write(boards: &[Board], mut w: impl Write) -> io::Result<()> {
@@ -40,3 +42,136 @@ pub fn read(mut r: impl Read) -> io::Result<Vec<Board>> {
 
     Ok(boards)
 }
+
+/// Compare two sorted lists of boards, such as an old and a new list from
+/// [`read`].
+///
+/// Returns `(added, removed)`: the boards only in `new`, and the boards only in
+/// `old`.  Both are sorted.
+pub fn diff(old: &[Board], new: &[Board]) -> (Vec<Board>, Vec<Board>) {
+    let mut added = Vec::new();
+    let mut removed = Vec::new();
+
+    let mut old = old.iter().copied().peekable();
+    let mut new = new.iter().copied().peekable();
+
+    loop {
+        match (old.peek(), new.peek()) {
+            (Some(o), Some(n)) if o < n => removed.extend(old.next()),
+            (Some(o), Some(n)) if o > n => added.extend(new.next()),
+            (Some(_), Some(_)) => {
+                old.next();
+                new.next();
+            }
+            (Some(_), None) => removed.extend(old.next()),
+            (None, Some(_)) => added.extend(new.next()),
+            (None, None) => break,
+        }
+    }
+
+    (added, removed)
+}
+
+/// Keep only the boards matching a predicate, checking them in parallel.
+///
+/// The order of `boards` is preserved, so a sorted list stays sorted.
+pub fn par_filter(boards: &[Board], pred: impl Fn(Board) -> bool + Sync) -> Vec<Board> {
+    boards
+        .par_iter()
+        .copied()
+        .filter(|&board| pred(board))
+        .collect()
+}
+
+/// Check whether a board is in a sorted list of boards, like the list from
+/// [`read`].
+///
+/// This is a binary search, so the list can be used directly instead of being
+/// copied into a `HashSet`.
+pub fn contains(sorted: &[Board], board: Board) -> bool {
+    sorted.binary_search(&board).is_ok()
+}
+
+/// Dense `u32` ids for a sorted list of boards, like the list from [`read`].
+///
+/// Each board's id is its position in the list.  Storing ids instead of boards
+/// takes half the memory.
+pub struct BoardIndex {
+    boards: Vec<Board>,
+}
+
+impl BoardIndex {
+    /// Number the given boards.
+    ///
+    /// The boards must be sorted, without duplicates.  In debug mode, this is
+    /// checked.
+    pub fn new(boards: Vec<Board>) -> BoardIndex {
+        assert!(boards.len() <= u32::MAX as usize);
+        debug_assert!(boards.windows(2).all(|pair| pair[0] < pair[1]));
+
+        BoardIndex { boards }
+    }
+
+    /// Find the id of a board, if it's in the list.
+    pub fn id_of(&self, board: Board) -> Option<u32> {
+        self.boards.binary_search(&board).ok().map(|idx| idx as u32)
+    }
+
+    /// Find the board with the given id.
+    ///
+    /// Panics if the id is out of range.
+    pub fn board_of(&self, id: u32) -> Board {
+        self.boards[id as usize]
+    }
+
+    pub fn len(&self) -> usize {
+        self.boards.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.boards.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        board_list::{contains, diff, read, write, BoardIndex},
+        gameplay::Board,
+    };
+
+    #[test]
+    fn diff_sorted() {
+        let old = [Board(1), Board(2), Board(4), Board(8)];
+        let new = [Board(0), Board(2), Board(3), Board(8), Board(9)];
+
+        let (added, removed) = diff(&old, &new);
+
+        assert_eq!(added, [Board(0), Board(3), Board(9)]);
+        assert_eq!(removed, [Board(1), Board(4)]);
+        assert_eq!(diff(&new, &new), (vec![], vec![]));
+    }
+
+    #[test]
+    fn index() {
+        let boards = vec![Board(0), Board(0b1111), Board(0xFFFFF_FFFFF)];
+
+        let mut file = Vec::new();
+        write(&boards, &mut file).unwrap();
+        let index = BoardIndex::new(read(file.as_slice()).unwrap());
+
+        assert_eq!(index.len(), 3);
+        for (id, &board) in boards.iter().enumerate() {
+            assert_eq!(index.id_of(board), Some(id as u32));
+            assert_eq!(index.board_of(id as u32), board);
+        }
+
+        assert_eq!(index.id_of(Board(0b11)), None);
+
+        for &board in &boards {
+            assert!(contains(&boards, board));
+        }
+        assert!(!contains(&boards, Board(0b11)));
+        assert!(!contains(&[], Board(0)));
+    }
+}
@@ -1,10 +1,14 @@
-use std::collections::HashSet;
+use std::{
+    collections::{BTreeSet, HashSet},
+    fmt::Write,
+};
 
 use bitvec::prelude::*;
+use rayon::prelude::*;
 use smallvec::SmallVec;
 
 use crate::{
-    gameplay::{Board, Orientation, Physics, Piece, Shape},
+    gameplay::{Board, Orientation, Physics, Piece, Shape, SpinKind},
     queue::Queue,
     vector::Placements,
 };
@@ -104,17 +108,165 @@ impl BrokenBoard {
         Board(new)
     }
 
+    /// Reflect the board left-to-right, along with every piece in it.
+    ///
+    /// J and L pieces swap, and so do S and Z.  So the queues which build the
+    /// mirrored board are [mirrored too](Queue::mirror_shapes).
+    pub fn mirror(&self) -> Self {
+        let mut pieces: SmallVec<[BrokenPiece; 10]> = self
+            .pieces
+            .iter()
+            .map(|&piece| {
+                let shape = piece.shape.mirror();
+
+                BrokenPiece {
+                    low_mino: piece.board().mirror().0.trailing_zeros() as u8,
+                    shape,
+                    orientation: piece.orientation.mirror().canonical(shape),
+                    rows: piece.rows,
+                }
+            })
+            .collect();
+        pieces.sort_unstable();
+
+        BrokenBoard {
+            board: self.board.mirror(),
+            cleared_rows: self.cleared_rows,
+            pieces,
+        }
+    }
+
+    /// Draw the board as an SVG image, with each piece in its own color.
+    ///
+    /// The drawing matches the web solver's:  each cell is 20 units square,
+    /// with a lighter strip on top and a shadow below, and there is a margin
+    /// of two rows above the board.  Cleared lines are drawn full.  Garbage
+    /// is gray.
+    pub fn to_svg(&self) -> String {
+        const BACKGROUND: &str = "#F3F3ED";
+        const SHADOW: &str = "#E7E7E2";
+        const GARBAGE: (&str, &str) = ("#686868", "#949494");
+
+        fn top_color(shape: Shape) -> &'static str {
+            [
+                "#43D3FF", "#1BA6F9", "#FFBF60", "#FFF952", "#88EE86", "#E56ADD", "#FF9484",
+            ][shape as usize]
+        }
+
+        let pieces: Vec<(Shape, Board)> = self
+            .pieces
+            .iter()
+            .map(|&piece| (piece.shape, piece.board()))
+            .collect();
+        let bits = self.to_broken_bitboard();
+
+        // (x, y, regular color, top color), from the top row down
+        let mut cells = Vec::new();
+
+        for row in (0..4).rev() {
+            for col in 0..10 {
+                if !bits.get(row, col) {
+                    continue;
+                }
+
+                let colors = match pieces.iter().find(|(_, board)| board.get(row, col)) {
+                    Some(&(shape, _)) => (shape.color(), top_color(shape)),
+                    None => GARBAGE,
+                };
+
+                cells.push((20 * col as i32, 20 * (5 - row as i32), colors));
+            }
+        }
+
+        let mut svg = String::new();
+        let rect = |svg: &mut String, x, y, width, height, fill| {
+            writeln!(
+                svg,
+                r#"<rect x="{}" y="{}" width="{}" height="{}" fill="{}"/>"#,
+                x, y, width, height, fill
+            )
+            .unwrap();
+        };
+
+        svg.push_str(
+            r#"<svg xmlns="http://www.w3.org/2000/svg" width="200" height="120" viewBox="0 0 200 120">"#,
+        );
+        svg.push('\n');
+        writeln!(
+            svg,
+            r#"<rect x="0" y="0" width="100%" height="100%" fill="{}"/>"#,
+            BACKGROUND
+        )
+        .unwrap();
+
+        for &(x, y, (_, top)) in &cells {
+            rect(&mut svg, x + 5, y + 7, 20, 20, SHADOW);
+            rect(&mut svg, x, y - 4, 20, 4, top);
+        }
+        for &(x, y, (regular, _)) in &cells {
+            rect(&mut svg, x, y, 20, 20, regular);
+        }
+
+        svg.push_str("</svg>\n");
+        svg
+    }
+
+    /// Color each cell the way fumen does, so a fumen page can be built
+    /// directly from a solution.
+    ///
+    /// Cells are in the same order as the bits of a [`Board`]:  bottom row
+    /// first, left to right.  Each one is 0 if empty, 8 for garbage, or
+    /// fumen's number for the shape of the piece covering it:  I = 1, L = 2,
+    /// O = 3, Z = 4, T = 5, J = 6, S = 7.  Cleared lines stay in place, like
+    /// in [`to_broken_bitboard`](BrokenBoard::to_broken_bitboard).
+    pub fn to_fumen_field(&self) -> [u8; 40] {
+        fn fumen_color(shape: Shape) -> u8 {
+            match shape {
+                Shape::I => 1,
+                Shape::L => 2,
+                Shape::O => 3,
+                Shape::Z => 4,
+                Shape::T => 5,
+                Shape::J => 6,
+                Shape::S => 7,
+            }
+        }
+
+        let mut field = [0; 40];
+        let bits = self.to_broken_bitboard().0;
+
+        for (idx, cell) in field.iter_mut().enumerate() {
+            if bits & (1 << idx) != 0 {
+                *cell = 8;
+            }
+        }
+
+        for &piece in &self.pieces {
+            let mut cells = piece.board().0;
+            while cells != 0 {
+                field[cells.trailing_zeros() as usize] = fumen_color(piece.shape);
+                cells &= cells - 1;
+            }
+        }
+
+        field
+    }
+
     pub fn place(&self, piece: Piece) -> Self {
+        let (board, full_lines) = piece.place_clearing(self.board);
+
         let mut new = BrokenBoard {
-            board: piece.place(self.board),
+            board,
             cleared_rows: 0,
             pieces: self.pieces.clone(),
         };
 
         let cleared_count = self.cleared_rows.count_ones();
 
+        // Lines already cleared sit at the bottom of the board, so the rest
+        // of the mask lines up with the rows that haven't been cleared yet.
+        let mut full_lines = full_lines >> cleared_count;
         let minoes = piece.as_board().0 >> (cleared_count * 10);
-        let field = (self.board.0 >> (cleared_count * 10)) | minoes;
 
         let mut row_mask = 0b1111111111;
         let mut rows = 0;
@@ -128,11 +280,12 @@ impl BrokenBoard {
                 if minoes & row_mask != 0 {
                     rows |= row_bit;
                 }
-                if field & row_mask == row_mask {
+                if full_lines & 1 != 0 {
                     new.cleared_rows |= row_bit;
                 }
 
                 row_mask <<= 10;
+                full_lines >>= 1;
             }
         }
 
@@ -149,6 +302,15 @@ impl BrokenBoard {
         new
     }
 
+    /// Classify a piece about to be [placed](BrokenBoard::place) in this
+    /// board as a T-spin or not.
+    ///
+    /// Fumens and bitboards only record where minoes ended up, so this has to
+    /// be asked before the piece lands.  See [`Piece::spin_kind`].
+    pub fn last_placement_spin(&self, piece: Piece) -> SpinKind {
+        piece.spin_kind(self.board)
+    }
+
     pub fn encode(&self) -> BitVec {
         let mut bv = BitVec::new();
 
@@ -234,15 +396,15 @@ impl BrokenBoard {
             return false;
         }
 
-        let mut board = self.to_broken_bitboard().0;
+        let mut board = self.to_broken_bitboard();
 
         // pieces are contained in the board, and do not overlap
         for piece in &self.pieces {
-            let piece_board = piece.board().0;
-            if board & piece_board != piece_board {
+            let piece_board = piece.board();
+            if !board.contains_all(piece_board) {
                 return false;
             }
-            board ^= piece_board;
+            board.0 ^= piece_board.0;
         }
 
         // it's okay if `board != 0`; that means there was initial garbage
@@ -250,6 +412,38 @@ impl BrokenBoard {
         true
     }
 
+    /// Combine the pieces of two boards built on the same garbage.
+    ///
+    /// Returns `None` if the boards started from different garbage, or if any
+    /// of their pieces overlap.  Rows which become full are cleared.
+    ///
+    /// This makes it possible to solve separate regions of a board
+    /// independently, then put the solutions together.
+    pub fn try_merge(&self, other: &BrokenBoard) -> Option<BrokenBoard> {
+        let garbage = self.garbage();
+        if garbage != other.garbage() {
+            return None;
+        }
+
+        let mut pieces = self.pieces.clone();
+        pieces.extend(other.pieces.iter().copied());
+
+        let mut field = garbage;
+        for piece in &pieces {
+            field = field.overlay(piece.board())?;
+        }
+
+        let mut merged = BrokenBoard::from_garbage(field.0);
+        merged.pieces = pieces;
+        merged.pieces.sort_unstable();
+
+        if merged.is_valid() {
+            Some(merged)
+        } else {
+            None
+        }
+    }
+
     /// Determine if a piece can be placed in this board.
     ///
     /// If `Some(p)` is returned, then:
@@ -304,23 +498,93 @@ impl BrokenBoard {
         Some(p)
     }
 
-    /// Run a search to find all queues that can produce this board without
-    /// holding.
-    pub fn supporting_queues(&self, physics: Physics) -> Vec<Queue> {
+    /// The garbage this board started from: every filled cell not covered by
+    /// a tracked piece.
+    ///
+    /// Like [`to_broken_bitboard`](BrokenBoard::to_broken_bitboard), cleared
+    /// lines stay in place.
+    pub fn garbage(&self) -> Board {
         let mut garbage = self.to_broken_bitboard().0;
 
         for &piece in &self.pieces {
             garbage ^= piece.board().0;
         }
 
+        Board(garbage)
+    }
+
+    /// Find every queue which can build this exact board, with or without
+    /// holding.
+    ///
+    /// This is [`supporting_queues`](BrokenBoard::supporting_queues) together
+    /// with every queue that can be [unheld](Queue::unhold_many) into one of
+    /// them.  Each queue has one shape per piece in the board; if a longer
+    /// queue leaves its last shape in hold, check its prefix.
+    ///
+    /// Comparing against all the queues of a bag shows which orderings this
+    /// board doesn't handle.
+    pub fn robust_against(&self, physics: Physics) -> BTreeSet<Queue> {
+        Queue::unhold_many(&self.supporting_queues(physics))
+            .into_iter()
+            .collect()
+    }
+
+    /// Find which rows can never be cleared by placing the shapes in `queue`,
+    /// in order, without holding.
+    ///
+    /// The result is a bit vector like
+    /// [`cleared_rows`](BrokenBoard::cleared_rows).  A row is clearable if any
+    /// sequence of placements clears it, after any number of pieces, even if
+    /// the rest of the queue can't be placed afterwards.  Rows which are
+    /// already cleared are clearable.
+    pub fn uncleanable_rows(&self, queue: Queue, physics: Physics) -> u8 {
+        let mut cleared = self.cleared_rows;
+
+        // Only the rows matter, not which pieces are where.
         let mut prev = HashSet::new();
-        prev.insert((BrokenBoard::from_garbage(garbage), Queue::empty()));
+        prev.insert(BrokenBoard {
+            pieces: SmallVec::new(),
+            ..self.clone()
+        });
+
+        for shape in queue {
+            let mut next = HashSet::new();
+
+            for board in prev {
+                for (piece, _) in Placements::place(board.board, shape, physics).canonical() {
+                    let mut new = board.place(piece);
+                    new.pieces.clear();
+
+                    cleared |= new.cleared_rows;
+                    next.insert(new);
+                }
+            }
+
+            prev = next;
+        }
+
+        !cleared & 0b1111
+    }
+
+    /// Check whether the pieces of this board can actually be placed, one at
+    /// a time, in some order.
+    ///
+    /// [`is_valid`](BrokenBoard::is_valid) only checks that the pieces fit
+    /// together.  A board can pass that check and still be impossible to
+    /// build, for example if a piece would have to float.
+    ///
+    /// This is the same as checking that
+    /// [`supporting_queues`](BrokenBoard::supporting_queues) is nonempty, but
+    /// it's much cheaper, since the order of pieces isn't tracked.
+    pub fn has_valid_build_order(&self, physics: Physics) -> bool {
+        let mut prev = HashSet::new();
+        prev.insert(BrokenBoard::from_garbage(self.garbage().0));
 
         for _ in 0..self.pieces.len() {
             let mut next = HashSet::new();
 
-            for (board, queue) in prev {
-                let mut placeable: Vec<Piece> = self
+            for board in prev {
+                let placeable: Vec<Piece> = self
                     .pieces
                     .iter()
                     .filter_map(|&p| board.placeable(p))
@@ -333,25 +597,118 @@ impl BrokenBoard {
 
                     for (piece, _) in Placements::place(board.board, shape, physics).canonical() {
                         if placeable.contains(&piece) {
-                            let pair = (board.place(piece), queue.push_last(shape));
+                            next.insert(board.place(piece));
+                        }
+                    }
+                }
+            }
+
+            if next.is_empty() {
+                return false;
+            }
 
-                            next.insert(pair);
+            prev = next;
+        }
 
-                            let index = placeable.iter().position(|p| p == &piece).unwrap();
-                            placeable.swap_remove(index);
+        true
+    }
 
-                            if !placeable.iter().any(|p| p.shape == shape) {
-                                break;
-                            }
-                        }
+    /// Run a search to find all queues that can produce this board without
+    /// holding.
+    ///
+    /// The queues are sorted by [`Queue::natural_order_key`].
+    pub fn supporting_queues(&self, physics: Physics) -> Vec<Queue> {
+        self.supporting_queues_capped(physics, None).0
+    }
+
+    /// Like [`supporting_queues`](BrokenBoard::supporting_queues), but keep the
+    /// search from growing too large.
+    ///
+    /// The search keeps a frontier of partially built boards, which can grow
+    /// factorially for boards with many interchangeable pieces.  As soon as it
+    /// reaches `max_frontier` entries, the rest of that stage is dropped, and
+    /// the search carries on with the boards it kept.
+    ///
+    /// Returns the queues found, and whether the search was cut short.  If it
+    /// was, the queues are still correct, but some are missing.
+    pub fn supporting_queues_capped(
+        &self,
+        physics: Physics,
+        max_frontier: Option<usize>,
+    ) -> (Vec<Queue>, bool) {
+        // Partial boards expanded at once, between checks of the frontier size.
+        const CHUNK: usize = 1024;
+
+        let max_frontier = max_frontier.unwrap_or(usize::MAX);
+        let mut truncated = false;
+
+        let mut prev = vec![(BrokenBoard::from_garbage(self.garbage().0), Queue::empty())];
+
+        for _ in 0..self.pieces.len() {
+            let mut next = HashSet::new();
+
+            'stage: for chunk in prev.chunks(CHUNK) {
+                // Each partial board is expanded independently, so each chunk
+                // is spread across threads.
+                let expanded: Vec<Vec<(BrokenBoard, Queue)>> = chunk
+                    .par_iter()
+                    .map(|(board, queue)| self.place_next(board, *queue, physics))
+                    .collect();
+
+                for pair in expanded.into_iter().flatten() {
+                    if next.len() >= max_frontier && !next.contains(&pair) {
+                        truncated = true;
+                        break 'stage;
                     }
+                    next.insert(pair);
                 }
             }
 
-            prev = next;
+            prev = next.into_iter().collect();
         }
 
-        prev.iter().map(|(_, queue)| *queue).collect()
+        let mut queues: Vec<Queue> = prev.iter().map(|(_, queue)| *queue).collect();
+        queues.sort_unstable_by_key(|q| q.natural_order_key());
+        (queues, truncated)
+    }
+
+    /// Every way to place one more of this board's pieces into a partial
+    /// `board`, built by `queue`, for
+    /// [`supporting_queues`](BrokenBoard::supporting_queues).
+    fn place_next(
+        &self,
+        board: &BrokenBoard,
+        queue: Queue,
+        physics: Physics,
+    ) -> Vec<(BrokenBoard, Queue)> {
+        let mut next = Vec::new();
+
+        let mut placeable: Vec<Piece> = self
+            .pieces
+            .iter()
+            .filter_map(|&p| board.placeable(p))
+            .collect();
+
+        for shape in Shape::ALL {
+            if !placeable.iter().any(|p| p.shape == shape) {
+                continue;
+            }
+
+            for (piece, _) in Placements::place(board.board, shape, physics).canonical() {
+                if placeable.contains(&piece) {
+                    next.push((board.place(piece), queue.push_last(shape)));
+
+                    let index = placeable.iter().position(|p| p == &piece).unwrap();
+                    placeable.swap_remove(index);
+
+                    if !placeable.iter().any(|p| p.shape == shape) {
+                        break;
+                    }
+                }
+            }
+        }
+
+        next
     }
 }
 
@@ -379,3 +736,365 @@ impl BrokenPiece {
         Board(broken)
     }
 }
+
+/// Pick a few solutions which, between them, can be built from every queue in
+/// `all_queues` (with hold).
+///
+/// This is set cover, solved greedily: repeatedly take the solution that
+/// handles the most queues not yet handled, until none of the rest help.  The
+/// result isn't always the smallest possible, but it's usually close.  It's in
+/// the order picked, so the most useful solution comes first.  Queues which no
+/// solution handles are ignored.
+///
+/// A queue one shape longer than a solution is handled if the solution can be
+/// built while leaving the extra piece in hold.  Anything past that is never
+/// drawn.
+pub fn minimal_cover(
+    solutions: &[BrokenBoard],
+    all_queues: &[Queue],
+    physics: Physics,
+) -> Vec<BrokenBoard> {
+    let covers: Vec<HashSet<Queue>> = solutions
+        .iter()
+        .map(|solution| {
+            let supporting = solution.supporting_queues(physics);
+            let with_held: Vec<Queue> = supporting
+                .iter()
+                .flat_map(|&queue| Shape::ALL.map(|shape| queue.push_last(shape)))
+                .collect();
+
+            let len = supporting.first().map_or(0, |queue| queue.len());
+            let mut handled = HashSet::new();
+            handled.extend(Queue::unhold_many(&supporting));
+            handled.extend(Queue::unhold_many(&with_held));
+
+            all_queues
+                .iter()
+                .copied()
+                .filter(|&queue| {
+                    let drawn: Queue = queue.take(len as usize + 1).collect();
+                    handled.contains(&drawn)
+                })
+                .collect()
+        })
+        .collect();
+
+    let mut uncovered: HashSet<Queue> = all_queues.iter().copied().collect();
+    let mut picked = Vec::new();
+
+    loop {
+        let best = covers
+            .iter()
+            .enumerate()
+            .map(|(idx, cover)| (cover.intersection(&uncovered).count(), idx))
+            .max_by_key(|&(count, idx)| (count, std::cmp::Reverse(idx)));
+
+        match best {
+            Some((count, idx)) if count > 0 => {
+                uncovered.retain(|queue| !covers[idx].contains(queue));
+                picked.push(solutions[idx].clone());
+            }
+            _ => return picked,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        brokenboard::{minimal_cover, BrokenBoard},
+        gameplay::{Board, Orientation, Physics, Piece, Shape, SpinKind},
+        queue::Queue,
+    };
+
+    #[test]
+    fn clear_order() {
+        let garbage = 0b0000111111_1111110000;
+        let start = BrokenBoard::from_garbage(garbage);
+
+        let i = |col, row| Piece {
+            shape: Shape::I,
+            col,
+            row,
+            orientation: Orientation::North,
+        };
+
+        // Cleared lines are moved to the bottom of the plain board, so the
+        // second piece goes in row 1 either way.
+        let bottom_first = start.place(i(0, 0)).place(i(6, 1));
+        let second_first = start.place(i(6, 1)).place(i(0, 1));
+
+        assert!(bottom_first.is_valid());
+        assert_eq!(bottom_first.cleared_rows, 0b0011);
+        assert_eq!(bottom_first, second_first);
+    }
+
+    #[test]
+    fn cover() {
+        let start = BrokenBoard::from_garbage(0b1111110000_1111110000);
+
+        let piece = |shape, orientation, col, row| Piece {
+            shape,
+            col,
+            row,
+            orientation,
+        };
+        let north = |shape, col, row| piece(shape, Orientation::North, col, row);
+
+        let ll =
+            start
+                .place(north(Shape::L, 1, 0))
+                .place(piece(Shape::L, Orientation::South, 0, 0));
+        let ii = start
+            .place(north(Shape::I, 0, 0))
+            .place(north(Shape::I, 0, 1));
+        let oo = start
+            .place(north(Shape::O, 0, 0))
+            .place(north(Shape::O, 2, 0));
+
+        // Every queue of three I and O pieces.  Two of a kind can always be
+        // placed, holding the odd one out.
+        let queues: Vec<Queue> = (0..8)
+            .map(|bits: u8| {
+                (0..3)
+                    .map(|i| {
+                        if bits >> i & 1 == 0 {
+                            Shape::I
+                        } else {
+                            Shape::O
+                        }
+                    })
+                    .collect()
+            })
+            .collect();
+
+        let cover = minimal_cover(&[ll, ii.clone(), oo.clone()], &queues, Physics::SRS);
+        assert_eq!(cover, [ii, oo]);
+    }
+
+    #[test]
+    fn mirror() {
+        let start = BrokenBoard::from_garbage(0b1111110000_1111110000);
+        let piece = |shape, orientation, col, row| Piece {
+            shape,
+            col,
+            row,
+            orientation,
+        };
+
+        let ll = start
+            .place(piece(Shape::L, Orientation::North, 1, 0))
+            .place(piece(Shape::L, Orientation::South, 0, 0));
+        let jj = BrokenBoard::from_garbage(0b0000111111_0000111111)
+            .place(piece(Shape::J, Orientation::North, 6, 0))
+            .place(piece(Shape::J, Orientation::South, 7, 0));
+
+        assert_eq!(ll.mirror(), jj);
+        assert_eq!(jj.mirror(), ll);
+        assert!(ll.mirror().is_valid());
+
+        let mirrored: Vec<Queue> = ll
+            .supporting_queues(Physics::SRS)
+            .into_iter()
+            .map(Queue::mirror_shapes)
+            .collect();
+        assert_eq!(mirrored, jj.supporting_queues(Physics::SRS));
+    }
+
+    #[test]
+    fn fumen_field() {
+        let board = BrokenBoard::from_garbage(0b0000001111_0011111111)
+            .place(Piece {
+                shape: Shape::T,
+                col: 4,
+                row: 1,
+                orientation: Orientation::North,
+            })
+            .place(Piece {
+                shape: Shape::O,
+                col: 8,
+                row: 0,
+                orientation: Orientation::North,
+            });
+
+        let field = board.to_fumen_field();
+
+        // The O piece clears the bottom row, which stays in place.
+        assert_eq!(board.cleared_rows, 0b0001);
+        assert_eq!(field[..10], [8, 8, 8, 8, 8, 8, 8, 8, 3, 3]);
+        assert_eq!(field[10..20], [8, 8, 8, 8, 5, 5, 5, 0, 3, 3]);
+        assert_eq!(field[20..30], [0, 0, 0, 0, 0, 5, 0, 0, 0, 0]);
+        assert_eq!(field[30..], [0; 10]);
+    }
+
+    #[test]
+    fn supporting_queues() {
+        let start = BrokenBoard::from_garbage(0b1111000000_1111000000);
+        let north = |shape, col, row| Piece {
+            shape,
+            col,
+            row,
+            orientation: Orientation::North,
+        };
+
+        // The bottom I must come before the top one.  The O can come anytime.
+        let iio = start
+            .place(north(Shape::I, 0, 0))
+            .place(north(Shape::I, 0, 1))
+            .place(north(Shape::O, 4, 0));
+        let ooo = start
+            .place(north(Shape::O, 0, 0))
+            .place(north(Shape::O, 2, 0))
+            .place(north(Shape::O, 4, 0));
+
+        let names = |board: &BrokenBoard| -> Vec<String> {
+            let mut names: Vec<String> = board
+                .supporting_queues(Physics::SRS)
+                .into_iter()
+                .map(Queue::to_string)
+                .collect();
+            names.sort();
+            names
+        };
+        assert_eq!(names(&iio), ["IIO", "IOI", "OII"]);
+        assert_eq!(names(&ooo), ["OOO"]);
+
+        // Same as expanding each stage one board at a time.
+        for board in [iio, ooo] {
+            let mut prev = vec![(BrokenBoard::from_garbage(board.garbage().0), Queue::empty())];
+            for _ in 0..board.pieces.len() {
+                let mut next: Vec<(BrokenBoard, Queue)> = prev
+                    .iter()
+                    .flat_map(|(partial, queue)| board.place_next(partial, *queue, Physics::SRS))
+                    .collect();
+                next.sort_unstable();
+                next.dedup();
+                prev = next;
+            }
+
+            let mut serial: Vec<Queue> = prev.into_iter().map(|(_, queue)| queue).collect();
+            serial.sort_unstable_by_key(|q| q.natural_order_key());
+            assert_eq!(serial, board.supporting_queues(Physics::SRS));
+        }
+    }
+
+    #[test]
+    fn supporting_queues_capped() {
+        let start = BrokenBoard::from_garbage(0b1111000000_1111000000);
+        let north = |shape, col, row| Piece {
+            shape,
+            col,
+            row,
+            orientation: Orientation::North,
+        };
+        let iio = start
+            .place(north(Shape::I, 0, 0))
+            .place(north(Shape::I, 0, 1))
+            .place(north(Shape::O, 4, 0));
+        let all = iio.supporting_queues(Physics::SRS);
+
+        // The largest stage holds three boards.
+        assert_eq!(
+            iio.supporting_queues_capped(Physics::SRS, None),
+            (all.clone(), false)
+        );
+        assert_eq!(
+            iio.supporting_queues_capped(Physics::SRS, Some(3)),
+            (all.clone(), false)
+        );
+
+        for cap in 1..3 {
+            let (queues, truncated) = iio.supporting_queues_capped(Physics::SRS, Some(cap));
+            assert!(truncated);
+            assert!(!queues.is_empty());
+            assert!(queues.len() < all.len());
+            assert!(queues.iter().all(|q| all.contains(q)));
+        }
+    }
+
+    #[test]
+    fn spins() {
+        let garbage = |rows| BrokenBoard::from_garbage(Board::from_display_rows(rows).unwrap().0);
+        let t = |col, row, orientation| Piece {
+            shape: Shape::T,
+            col,
+            row,
+            orientation,
+        };
+
+        // T-spin triple:  all four corners filled.
+        let tst = garbage(["__________", "####_#####", "###__#####", "####_#####"]);
+        let piece = t(3, 0, Orientation::West);
+        assert_eq!(tst.last_placement_spin(piece), SpinKind::Full);
+        assert_eq!(tst.place(piece).cleared_rows, 0b0111);
+
+        // STSD:  the open back corner leaves the top row, but both corners on
+        // the pointed side are filled.
+        let stsd = garbage(["__________", "####______", "###__#####", "####_#####"]);
+        assert_eq!(stsd.last_placement_spin(piece), SpinKind::Full);
+        assert_eq!(stsd.place(piece).cleared_rows, 0b0011);
+
+        // Opening the other top corner instead leaves only one front corner:
+        // a mini.
+        let mini = garbage(["__________", "###__#####", "###__#####", "####_#####"]);
+        assert_eq!(mini.last_placement_spin(piece), SpinKind::Mini);
+
+        // Dropped flat onto the floor.
+        let flat = garbage(["__________", "__________", "__________", "___#######"]);
+        let piece = t(0, 0, Orientation::North);
+        assert_eq!(flat.last_placement_spin(piece), SpinKind::None);
+        assert_eq!(flat.place(piece).cleared_rows, 0b0001);
+    }
+
+    #[test]
+    fn try_merge() {
+        let o = |col| Piece {
+            shape: Shape::O,
+            col,
+            row: 0,
+            orientation: Orientation::North,
+        };
+
+        // The two O pieces fill both rows together, but not apart.
+        let start = BrokenBoard::from_garbage(0b1111110000_1111110000);
+        let left = start.place(o(0));
+        let right = start.place(o(2));
+
+        let merged = left.try_merge(&right).unwrap();
+        assert_eq!(merged, start.place(o(0)).place(o(2)));
+        assert_eq!(merged.cleared_rows, 0b0011);
+        assert_eq!(right.try_merge(&left), Some(merged));
+
+        // Overlapping pieces.
+        let empty = BrokenBoard::empty();
+        assert_eq!(empty.place(o(0)).try_merge(&empty.place(o(1))), None);
+        assert_eq!(left.try_merge(&left), None);
+
+        // Different garbage.
+        assert_eq!(empty.place(o(0)).try_merge(&start.place(o(2))), None);
+    }
+
+    #[test]
+    fn svg() {
+        let board = BrokenBoard::from_garbage(0b0000001111).place(Piece {
+            shape: Shape::I,
+            col: 4,
+            row: 0,
+            orientation: Orientation::North,
+        });
+
+        let svg = board.to_svg();
+        assert!(svg.starts_with("<svg "));
+        assert!(svg.ends_with("</svg>\n"));
+
+        // Garbage in the bottom-left corner, the I piece to its right.
+        assert!(svg.contains(r##"<rect x="0" y="100" width="20" height="20" fill="#686868"/>"##));
+        assert!(svg.contains(r##"<rect x="60" y="100" width="20" height="20" fill="#686868"/>"##));
+        assert!(svg.contains(r##"<rect x="80" y="100" width="20" height="20" fill="#41AFDE"/>"##));
+        assert!(svg.contains(r##"<rect x="140" y="96" width="20" height="4" fill="#43D3FF"/>"##));
+        assert!(!svg.contains(r##"x="160" y="100""##));
+
+        // One shadow, one top, and one cell for each mino, plus the background.
+        assert_eq!(svg.matches("<rect ").count(), 8 * 3 + 1);
+    }
+}
@@ -1,5 +1,9 @@
 //! Game data types and physics.
 
+use std::collections::HashSet;
+
+use crate::vector::{CustomKicks, Placements};
+
 /// A packed bit representation of a board.
 ///
 /// Bit 0 (the least significant bit) represents the bottom left of the board.
@@ -65,9 +69,12 @@ pub struct Board(pub u64);
 ///
 /// # SRS
 ///
-/// Methods on this struct use SRS.  For other rotation systems, use the
+/// Methods on this struct use SRS, except for [`half`] and [`rotate`], which
+/// take a [`Physics`].  For finding every placement at once, use the
 /// [`vector`] module.
 ///
+/// [`half`]:   Piece::half
+/// [`rotate`]: Piece::rotate
 /// [`vector`]: crate::vector
 #[derive(Clone, Copy, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
 pub struct Piece {
@@ -112,6 +119,56 @@ pub enum Orientation {
     West,
 }
 
+/// Error from [`Piece::as_board_checked`]: the contained piece has minoes above
+/// the bottom four rows of the board.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub struct AboveBoardError(pub Piece);
+
+impl std::fmt::Display for AboveBoardError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "piece {:?} extends above the board", self.0)
+    }
+}
+
+impl std::error::Error for AboveBoardError {}
+
+/// Each direction a piece can rotate.
+#[derive(Clone, Copy, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub enum Rotation {
+    /// A 90° clockwise rotation.
+    Clockwise,
+    /// A 180° half rotation.
+    Half,
+    /// A 90° counter-clockwise rotation.
+    CounterClockwise,
+}
+
+/// A single input that moves a piece.
+#[derive(Clone, Copy, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub enum Move {
+    /// Shift one column left.
+    Left,
+    /// Shift one column right.
+    Right,
+    /// Shift one row down.
+    Down,
+    /// Rotate in the given direction.
+    Rotate(Rotation),
+}
+
+/// Whether a placed T piece counts as a T-spin.
+///
+/// See [`Piece::spin_kind`].
+#[derive(Clone, Copy, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub enum SpinKind {
+    /// Not a T-spin.
+    None,
+    /// A T-spin mini:  three corners are filled, but not both front corners.
+    Mini,
+    /// A full T-spin.
+    Full,
+}
+
 /// Different rotation systems, distinguished by their handling of half
 /// rotations.
 ///
@@ -120,16 +177,96 @@ pub enum Orientation {
 /// vice versa.
 #[derive(Clone, Copy, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
 pub enum Physics {
-    /// Super Rotation System.  Uses only quarter rotations, where each rotation
-    /// checks five kick offsets.
+    /// Super Rotation System.  Each quarter rotation checks five kick offsets.
+    /// Half rotations only turn in place, as in guideline games with 180°
+    /// rotation enabled.
     SRS,
-    /// System used by Jstris.  Uses SRS quarter rotations and adds two kick
-    /// offsets for half rotations.
+    /// System used by Jstris.  Uses SRS rotations and adds a second kick
+    /// offset for half rotations.
     Jstris,
     /// System used by TETRIO.  Uses SRS+ quarter rotations (which are SRS with
     /// I-piece quarter rotations modified to be more intuitive) and adds six
     /// kick offsets for half rotations.
     Tetrio,
+    /// Kick tables loaded at runtime, for experimenting with other rotation
+    /// systems.  Slower to search than the built-in physics.
+    ///
+    /// The tables are borrowed for the rest of the program (see
+    /// [`CustomKicks::leak`]) so that `Physics` stays `Copy`.
+    Custom(&'static CustomKicks),
+}
+
+/// Weights for [`Board::complexity_score_with`].
+///
+/// Each field multiplies one feature of the board.  The defaults are a rough
+/// guess, so tune them to taste.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ComplexityWeights {
+    /// Per covered hole:  an empty cell with no path to the top of the board.
+    /// See [`Board::open_empty`].
+    pub holes: f64,
+    /// Per unit of variance in column heights.
+    pub height_variance: f64,
+    /// Per shape which cannot be placed anywhere.
+    pub blocked_shapes: f64,
+}
+
+impl Default for ComplexityWeights {
+    fn default() -> ComplexityWeights {
+        ComplexityWeights {
+            holes: 1.0,
+            height_variance: 0.5,
+            blocked_shapes: 2.0,
+        }
+    }
+}
+
+impl Physics {
+    /// Array of all rotation systems.
+    pub const ALL: [Physics; 3] = [Physics::SRS, Physics::Jstris, Physics::Tetrio];
+
+    /// Check whether every placement valid in SRS is also valid with this
+    /// physics.
+    ///
+    /// When searching placements for several physics at once, SRS can be
+    /// skipped if any of the others is a superset of it.  Custom kicks are
+    /// never assumed to be a superset.
+    pub fn is_srs_superset(self) -> bool {
+        match self {
+            Physics::SRS | Physics::Jstris => true,
+            Physics::Tetrio | Physics::Custom(_) => false,
+        }
+    }
+
+    /// Check whether rotating an O piece can ever move it.
+    ///
+    /// In every built-in rotation system, O pieces rotate in place, so all
+    /// four orientations are interchangeable.  Placement search relies on this
+    /// to take a shortcut for O pieces.  Custom kicks can only use the
+    /// shortcut if every O offset is `(0, 0)`.
+    pub fn o_can_kick(self) -> bool {
+        match self {
+            Physics::SRS | Physics::Jstris | Physics::Tetrio => false,
+            Physics::Custom(custom) => {
+                use Orientation::*;
+
+                [North, East, South, West].iter().any(|&initial| {
+                    (0..3).any(|direction| {
+                        custom
+                            .offsets(Shape::O, direction, initial)
+                            .iter()
+                            .any(|&offset| offset != (0, 0))
+                    })
+                })
+            }
+        }
+    }
+
+    /// The kicks this physics uses for the given shape, in the form used by
+    /// published kick tables.  See [`kick_offsets`](crate::vector::kick_offsets).
+    pub fn kicks_for(self, shape: Shape) -> crate::vector::KickOffsets {
+        crate::vector::kick_offsets(self, shape)
+    }
 }
 
 impl Board {
@@ -138,6 +275,41 @@ impl Board {
         Board(0)
     }
 
+    /// Move full lines to the bottom of the board, keeping the order of the
+    /// rest.  This is how [`Piece::place`] arranges cleared lines.
+    ///
+    /// Also reports which lines were full:  bit `n` of the mask is set if row
+    /// `n` was full.
+    #[must_use]
+    pub fn clear_full_lines(self) -> (Board, u8) {
+        let mut unordered_board = self.0;
+
+        let mut ordered_board = 0;
+        let mut complete_lines = 0;
+        let mut complete_lines_shift = 0;
+        let mut cleared = 0;
+
+        for row in (0..4).rev() {
+            let this_line = (unordered_board >> 30) & 0b1111111111;
+            unordered_board <<= 10;
+
+            if this_line == 0b1111111111 {
+                complete_lines <<= 10;
+                complete_lines |= this_line;
+                complete_lines_shift += 10;
+                cleared |= 1 << row;
+            } else {
+                ordered_board <<= 10;
+                ordered_board |= this_line;
+            }
+        }
+
+        ordered_board <<= complete_lines_shift;
+        ordered_board |= complete_lines;
+
+        (Board(ordered_board), cleared)
+    }
+
     /// Check whether the cell at the given row and column is set.
     ///
     /// Requires that 0 &le; `col` &le; 9 and 0 &le; `row` &le; 3.
@@ -151,6 +323,202 @@ impl Board {
         (self.0 & mask) != 0
     }
 
+    /// Parse a board drawn as text, **top row first**.
+    ///
+    /// Each row must be exactly 10 characters.  `_` is an empty cell, and any
+    /// other character is a filled cell, so the output of
+    /// [`to_display_rows`](Board::to_display_rows) and solution drawings like
+    /// `__IIII____` both work.  Returns `None` if any row has the wrong length.
+    ///
+    /// In the bit representation, bit 0 is the *bottom* left cell, so
+    /// `rows[3]` becomes bits 0&ndash;9 and `rows[0]` becomes bits 30&ndash;39.
+    pub fn from_display_rows(rows: [&str; 4]) -> Option<Board> {
+        let mut board = 0;
+
+        for (i, row) in rows.iter().enumerate() {
+            let cells: Vec<char> = row.chars().collect();
+            if cells.len() != 10 {
+                return None;
+            }
+
+            let shift = (3 - i) * 10;
+            for (col, &cell) in cells.iter().enumerate() {
+                if cell != '_' {
+                    board |= 1 << (shift + col);
+                }
+            }
+        }
+
+        Some(Board(board))
+    }
+
+    /// Draw the board as text, **top row first**, with `#` for filled cells
+    /// and `_` for empty cells.
+    ///
+    /// See [`from_display_rows`](Board::from_display_rows).
+    pub fn to_display_rows(self) -> [String; 4] {
+        let row = |row: i8| -> String {
+            (0..10)
+                .map(|col| if self.get(row, col) { '#' } else { '_' })
+                .collect()
+        };
+
+        [row(3), row(2), row(1), row(0)]
+    }
+
+    /// Check whether every cell set in `other` is also set in this board.
+    pub fn contains_all(self, other: Board) -> bool {
+        self.0 & other.0 == other.0
+    }
+
+    /// Check whether two boards match, except for cells in `ignore`.
+    ///
+    /// This is handy for comparing boards built on different garbage:  pass
+    /// the garbage cells of both as `ignore`.
+    pub fn equals_ignoring(self, other: Board, ignore: Board) -> bool {
+        (self.0 ^ other.0) & !ignore.0 & BOARD_MASK == 0
+    }
+
+    /// Keep only the cells inside a rectangular region, clearing everything
+    /// else.  The board keeps its size, so cells don't move.
+    ///
+    /// Ranges outside the board are clamped to it.
+    pub fn slice(self, cols: std::ops::Range<i8>, rows: std::ops::Range<i8>) -> Board {
+        let col_start = cols.start.clamp(0, 10);
+        let col_end = cols.end.clamp(col_start, 10);
+        let row_start = rows.start.clamp(0, 4);
+        let row_end = rows.end.clamp(row_start, 4);
+
+        let row_mask = (1u64 << col_end) - (1 << col_start);
+
+        let mut mask = 0;
+        for row in row_start..row_end {
+            mask |= row_mask << (row * 10);
+        }
+
+        Board(self.0 & mask)
+    }
+
+    /// Reflect the board left-to-right.
+    ///
+    /// Rows stay in place.  This is cheap enough to call for every board in a
+    /// search.
+    pub fn mirror(self) -> Board {
+        const ROW: u64 = 0b1111111111;
+
+        // Reversing all 40 bits mirrors each row, but also turns the board
+        // upside down, so swap the rows back.
+        let rotated = (self.0 & BOARD_MASK).reverse_bits() >> 24;
+
+        Board(
+            (rotated & ROW) << 30
+                | (rotated & ROW << 10) << 10
+                | (rotated >> 10) & ROW << 10
+                | rotated >> 30,
+        )
+    }
+
+    /// Reflect the board top-to-bottom.
+    ///
+    /// Gravity doesn't respect this symmetry, so the result is rarely a board
+    /// you could actually build.  It's mostly useful for keys.
+    pub fn flip(self) -> Board {
+        let mut flipped = 0;
+
+        for row in 0..4 {
+            let bits = (self.0 >> (row * 10)) & 0b1111111111;
+            flipped |= bits << ((3 - row) * 10);
+        }
+
+        Board(flipped)
+    }
+
+    /// Rotate the board by 180&deg;.  This is the same as both mirroring and
+    /// flipping it.
+    pub fn rotate_180(self) -> Board {
+        Board(self.0.reverse_bits() >> 24)
+    }
+
+    /// Pick a single representative of this board's symmetry class: the
+    /// smallest of the board, its [mirror](Board::mirror), its
+    /// [flip](Board::flip), and its [rotation](Board::rotate_180).
+    ///
+    /// Boards that are the same up to those symmetries have the same canonical
+    /// board.
+    pub fn canonical_symmetry(self) -> Board {
+        *[self, self.mirror(), self.flip(), self.rotate_180()]
+            .iter()
+            .min()
+            .unwrap()
+    }
+
+    /// Combine the cells of two boards.
+    ///
+    /// Returns `None` if any cell is set in both boards.  This is useful for
+    /// assembling a board from separate regions, where overlap is a mistake.
+    pub fn overlay(self, other: Board) -> Option<Board> {
+        if self.0 & other.0 != 0 {
+            None
+        } else {
+            Some(Board(self.0 | other.0))
+        }
+    }
+
+    /// Drop a single cell into a column, landing on top of the highest filled
+    /// cell there, or on the floor.
+    ///
+    /// This isn't something a real piece can do, but it's handy for building
+    /// boards by hand.  Returns `None` if the column is already filled to the
+    /// top.
+    ///
+    /// Requires that 0 &le; `col` &le; 9.
+    pub fn add_cell_dropped(self, col: u8) -> Option<Board> {
+        let col = col as i8;
+        let row = match (0..4).rev().find(|&row| self.get(row, col)) {
+            Some(3) => return None,
+            Some(top) => top + 1,
+            None => 0,
+        };
+
+        Some(Board(self.0 | 1 << (col + row * 10)))
+    }
+
+    /// Check whether a new piece of the given shape can spawn.
+    ///
+    /// The piece spawns just above the bottom four rows, as in [`Piece::new`].
+    /// Valid boards never fill those cells, so this only matters for boards
+    /// with extra cells above the usual 4&times;10 area, such as taller fields
+    /// or high garbage.
+    pub fn can_spawn(self, shape: Shape) -> bool {
+        !Piece::new(shape).collides_in(self)
+    }
+
+    /// Find every board that placing a single piece could produce, where the
+    /// piece clears at least one line.
+    ///
+    /// As with [`Piece::place`], cleared lines are moved to the bottom of the
+    /// board.  All shapes are tried.
+    pub fn clear_outcomes(self, physics: Physics) -> HashSet<Board> {
+        fn full_rows(board: Board) -> usize {
+            (0..4)
+                .filter(|row| (board.0 >> (row * 10)) & 0b1111111111 == 0b1111111111)
+                .count()
+        }
+
+        let before = full_rows(self);
+        let mut outcomes = HashSet::new();
+
+        for shape in Shape::ALL {
+            for (_, new_board) in Placements::place(self, shape, physics).canonical() {
+                if full_rows(new_board) > before {
+                    outcomes.insert(new_board);
+                }
+            }
+        }
+
+        outcomes
+    }
+
     /// Check whether the board has a cell that cannot be filled.
     ///
     /// If the two cells to the left and right of an empty cell are both full
@@ -167,6 +535,10 @@ impl Board {
     ///
     /// This check saves a lot of time by culling unusable boards early.
     pub fn has_isolated_cell(self) -> bool {
+        // There's no early-out for sparse boards.  An isolated cell needs at
+        // least 4 filled cells, but every board after the first piece has that
+        // many anyway, and counting bits costs more than the check below.
+
         // Combine rows in two ways:
         //   Is the column full?
         //   Is the column non-empty?
@@ -296,6 +668,234 @@ impl Board {
             || check_col(self, COL_6, LEFT_6)
             || check_col(self, COL_7, LEFT_7)
     }
+
+    /// Split the board into regions that pieces can never cross between.
+    ///
+    /// This uses the same test as [`has_imbalanced_split`]: if two adjacent
+    /// columns have a filled cell in every row between them, no piece can ever
+    /// touch empty cells on both sides, even after lines are cleared.
+    ///
+    /// Each region is returned as a board with every cell outside the region
+    /// filled, along with a bit vector of the columns in the region.  Regions
+    /// are in order from left to right.  Regions with no empty cells are left
+    /// out.
+    ///
+    /// ```text
+    ///   board     regions
+    /// #___#_____  #___######  #####_____
+    /// ##__#_____  ##__######  #####_____
+    /// ##__##____  ##__######  ######____
+    /// ###_###___  ###_######  #######___
+    /// ```
+    ///
+    /// Each region must be filled separately, but they aren't independent
+    /// problems: a line is only cleared once every region has filled it.
+    ///
+    /// [`has_imbalanced_split`]: Board::has_imbalanced_split
+    pub fn independent_regions(self) -> Vec<(Board, u16)> {
+        const COL_0: u64 = 0b0000000001_0000000001_0000000001_0000000001;
+
+        let mut regions = Vec::new();
+        let mut columns: u16 = 0;
+
+        for col in 0..10 {
+            columns |= 1 << col;
+
+            let col_mask = COL_0 << col;
+            let split = col == 9 || (self.0 | (self.0 >> 1)) & col_mask == col_mask;
+
+            if split {
+                let region_mask = (0..10)
+                    .filter(|c| columns & (1 << c) != 0)
+                    .fold(0, |mask, c| mask | COL_0 << c);
+
+                if !self.0 & region_mask != 0 {
+                    regions.push((Board(self.0 | (BOARD_MASK & !region_mask)), columns));
+                }
+
+                columns = 0;
+            }
+        }
+
+        regions
+    }
+
+    /// Color the board like a checkerboard, with the bottom-left cell white,
+    /// and count white empty cells minus black empty cells.
+    ///
+    /// Every I, J, L, O, S, and Z piece covers two white cells and two black
+    /// cells, wherever it's placed.  So if only those shapes are used, and no
+    /// lines are cleared, a region can only be filled exactly if its balance
+    /// is zero.  A T piece covers three of one color and one of the other, so
+    /// each T changes the balance by 2 in either direction.
+    ///
+    /// Clearing a line shifts the rows above it, which swaps their colors.  So
+    /// this argument only applies to regions filled without clearing lines, or
+    /// to each region between cleared lines separately.
+    pub fn checkerboard_balance(self) -> i8 {
+        const WHITE: u64 = 0b1010101010_0101010101_1010101010_0101010101;
+        const BLACK: u64 = WHITE ^ BOARD_MASK;
+
+        let empty = !self.0 & BOARD_MASK;
+        (empty & WHITE).count_ones() as i8 - (empty & BLACK).count_ones() as i8
+    }
+
+    /// Find the height of each column: one more than the row of its highest
+    /// filled cell, or 0 if it's empty.
+    ///
+    /// Holes don't matter, only the highest cell.
+    pub fn column_heights(self) -> [u8; 10] {
+        let mut heights = [0; 10];
+
+        for (col, height) in heights.iter_mut().enumerate() {
+            *height = (0..4)
+                .rev()
+                .find(|&row| self.get(row, col as i8))
+                .map_or(0, |row| row as u8 + 1);
+        }
+
+        heights
+    }
+
+    /// Find the highest filled cell in each column.
+    ///
+    /// ```text
+    ///   board     surface
+    /// __________  __________
+    /// #_________  #_________
+    /// #_##_###_#  __##_###_#
+    /// ##_#######  _#__#___#_
+    /// ```
+    ///
+    /// Cells under an overhang aren't part of the surface, even if they're
+    /// open from the side.  Empty columns have no cell in the surface.
+    pub fn surface(self) -> Board {
+        let filled_above = (self.0 >> 10) | (self.0 >> 20) | (self.0 >> 30);
+        Board(self.0 & !filled_above)
+    }
+
+    /// Find the wells: columns which are lower than both of their neighbors.
+    ///
+    /// Bit `n` of the result is set if column `n` is a well.  The walls count
+    /// as taller than any column.
+    ///
+    /// ```text
+    ///   board     wells
+    /// __________
+    /// #_________
+    /// #_##_###_#  _#__#___#_
+    /// ##########
+    /// ```
+    ///
+    /// These are the columns set up for a vertical I (or several, in a deep
+    /// well).  Unlike holes, wells are open from above.
+    pub fn wells(self) -> u16 {
+        let heights = self.column_heights();
+        let mut wells = 0;
+
+        for col in 0..10 {
+            let left = if col == 0 { u8::MAX } else { heights[col - 1] };
+            let right = if col == 9 { u8::MAX } else { heights[col + 1] };
+
+            if heights[col] < left && heights[col] < right {
+                wells |= 1 << col;
+            }
+        }
+
+        wells
+    }
+
+    /// Pack simple features of this board into bytes, for feeding to a
+    /// machine learning model.
+    ///
+    /// The layout is fixed:
+    ///
+    /// - bytes 0&ndash;9:  the height of each column (see
+    ///   [`column_heights`](Board::column_heights))
+    /// - bytes 10&ndash;19:  the number of empty cells in each column below its
+    ///   highest filled cell
+    /// - byte 20:  bumpiness, the sum of the height differences between
+    ///   neighboring columns
+    pub fn to_feature_bytes(self) -> [u8; 21] {
+        let heights = self.column_heights();
+        let mut bytes = [0; 21];
+
+        for col in 0..10 {
+            let filled = (0..4).filter(|&row| self.get(row, col as i8)).count() as u8;
+            bytes[col] = heights[col];
+            bytes[10 + col] = heights[col] - filled;
+        }
+
+        bytes[20] = heights.windows(2).map(|w| w[0].abs_diff(w[1])).sum();
+
+        bytes
+    }
+
+    /// Estimate how hard this board is to work with, using the default
+    /// [`ComplexityWeights`].
+    ///
+    /// Higher is harder.  This is only a heuristic, for ranking boards against
+    /// each other; the number itself means nothing.
+    pub fn complexity_score(self, physics: Physics) -> f64 {
+        self.complexity_score_with(physics, &ComplexityWeights::default())
+    }
+
+    /// Estimate how hard this board is to work with, like
+    /// [`complexity_score`](Board::complexity_score), with custom weights.
+    pub fn complexity_score_with(self, physics: Physics, weights: &ComplexityWeights) -> f64 {
+        let holes = (!self.0 & !self.open_empty().0 & BOARD_MASK).count_ones();
+
+        let heights = self.column_heights().map(f64::from);
+        let mean = heights.iter().sum::<f64>() / 10.0;
+        let variance = heights.iter().map(|h| (h - mean).powi(2)).sum::<f64>() / 10.0;
+
+        let blocked = Shape::ALL
+            .iter()
+            .filter(|&&shape| Placements::place(self, shape, physics).len() == 0)
+            .count();
+
+        weights.holes * holes as f64
+            + weights.height_variance * variance
+            + weights.blocked_shapes * blocked as f64
+    }
+
+    /// Find the empty cells which are connected to the top of the board.
+    ///
+    /// Starting from the empty cells in the top row, this flood fills through
+    /// empty cells left, right, up, and down.  Any other empty cells are
+    /// covered holes, enclosed by filled cells.
+    ///
+    /// ```text
+    ///   board        open       holes
+    /// __________  ░░░░░░░░░░  __________
+    /// ###_######  ___░______  __________
+    /// #_#___####  ___░░░____  _#________
+    /// ###_######  ___░______  __________
+    /// ```
+    ///
+    /// So the holes are `!board.0 & !board.open_empty().0`, within the bottom
+    /// 40 bits.
+    pub fn open_empty(self) -> Board {
+        const LEFT_9: u64 = 0b0111111111_0111111111_0111111111_0111111111;
+        const RIGHT_9: u64 = 0b1111111110_1111111110_1111111110_1111111110;
+        const TOP_ROW: u64 = 0b1111111111 << 30;
+
+        let empty = !self.0 & BOARD_MASK;
+        let mut open = empty & TOP_ROW;
+
+        loop {
+            let next = open
+                | (open >> 10 & empty)
+                | (open << 10 & empty)
+                | (open >> 1 & LEFT_9 & empty)
+                | (open << 1 & RIGHT_9 & empty);
+
+            if next == open {
+                return Board(open);
+            }
+            open = next;
+        }
+    }
 }
 
 impl Piece {
@@ -314,6 +914,26 @@ impl Piece {
         }
     }
 
+    /// Create a new piece of the given shape, centered on a board `width`
+    /// columns wide.
+    ///
+    /// Like [`new`](Piece::new), the piece faces north, just above the bottom
+    /// four rows.  When it can't be exactly centered, it leans left, so on a
+    /// 10-column board the I, J, L, S, T, and Z pieces start in column 3 and
+    /// the O piece starts in column 4, as in most games.
+    ///
+    /// Requires that the piece fits in `width` columns.
+    pub fn spawn_centered(shape: Shape, width: u8) -> Piece {
+        let piece = Piece::new(shape);
+        let (piece_width, _) = piece.bounding_box();
+        assert!(piece_width <= width);
+
+        Piece {
+            col: ((width - piece_width) / 2) as i8,
+            ..piece
+        }
+    }
+
     /// Pack a piece into a 16-bit number.
     ///
     /// The number returned will be strictly less than 0x4000 = 16384.
@@ -382,6 +1002,48 @@ impl Piece {
         Board(self.as_bits() & BOARD_MASK)
     }
 
+    /// Convert a piece into a board, like [`as_board`], but refuse to cut off
+    /// any minoes.
+    ///
+    /// If any mino is above the bottom four rows, returns an error instead.
+    /// Use this when validating outside data, where a piece that is too high is
+    /// a mistake rather than something to silently truncate.
+    ///
+    /// [`as_board`]: Piece::as_board
+    pub fn as_board_checked(self) -> Result<Board, AboveBoardError> {
+        let bits = self.as_bits();
+
+        if bits & !BOARD_MASK != 0 {
+            return Err(AboveBoardError(self));
+        }
+
+        Ok(Board(bits))
+    }
+
+    /// The width and height of the smallest box containing the piece, in that
+    /// order.
+    pub fn bounding_box(self) -> (u8, u8) {
+        let shape = PIECE_SHAPES[self.shape as usize][self.orientation as usize];
+
+        let columns = (shape | shape >> 10 | shape >> 20 | shape >> 30) & 0b1111111111;
+        let width = 64 - columns.leading_zeros();
+        let height = (64 - shape.leading_zeros()).div_ceil(10);
+
+        (width as u8, height as u8)
+    }
+
+    /// Which of the bottom four rows the piece touches, as a bit mask where
+    /// bit 0 is the bottom row.
+    ///
+    /// Like [`as_board`], rows above the board are cut off.
+    ///
+    /// [`as_board`]: Piece::as_board
+    pub fn occupied_rows(self) -> u8 {
+        let (_, height) = self.bounding_box();
+        let rows = ((1u16 << height) - 1) << self.row;
+        (rows & 0b1111) as u8
+    }
+
     /// Convert a piece into a bit board.  Exactly like [`as_board`], except
     /// without cutting off minoes above the four bottom rows.
     ///
@@ -410,7 +1072,7 @@ impl Piece {
     /// 3. Resting on a filled cell or the bottom of the board
     pub fn can_place(self, board: Board) -> bool {
         let bits = self.as_bits();
-        ((bits & BOARD_MASK) != 0) && ((bits & !BOARD_MASK) == 0) && self.down(board) == self
+        ((bits & BOARD_MASK) != 0) && ((bits & !BOARD_MASK) == 0) && self.try_down(board).is_none()
     }
 
     /// Place a piece into the board, and move full lines to the bottom of the
@@ -429,72 +1091,74 @@ impl Piece {
     /// lines have been cleared on the board already.
     #[must_use]
     pub fn place(self, board: Board) -> Board {
+        self.place_clearing(board).0
+    }
+
+    /// Like [`Piece::place`], but also report which lines were full.
+    ///
+    /// Bit `n` of the mask is set if row `n` of the board (before full lines
+    /// are moved) was full after placing the piece.  Lines which were already
+    /// full are included.
+    #[must_use]
+    pub fn place_clearing(self, board: Board) -> (Board, u8) {
         debug_assert!(self.can_place(board));
         debug_assert!((board.0 & self.as_bits()) == 0);
 
-        let mut unordered_board = board.0 | self.as_bits();
-
-        let mut ordered_board = 0;
-        let mut complete_lines = 0;
-        let mut complete_lines_shift = 0;
-
-        for _ in 0..4 {
-            let this_line = (unordered_board >> 30) & 0b1111111111;
-            unordered_board <<= 10;
-
-            if this_line == 0b1111111111 {
-                complete_lines <<= 10;
-                complete_lines |= this_line;
-                complete_lines_shift += 10;
-            } else {
-                ordered_board <<= 10;
-                ordered_board |= this_line;
-            }
-        }
-
-        ordered_board <<= complete_lines_shift;
-        ordered_board |= complete_lines;
-
-        Board(ordered_board)
+        Board(board.0 | self.as_bits()).clear_full_lines()
     }
 
     /// Shift a piece left.  If impossible, returns the piece unchanged.
     #[must_use]
     pub fn left(self, board: Board) -> Piece {
+        self.try_left(board).unwrap_or(self)
+    }
+
+    /// Shift a piece right.  If impossible, returns the piece unchanged.
+    #[must_use]
+    pub fn right(self, board: Board) -> Piece {
+        self.try_right(board).unwrap_or(self)
+    }
+
+    /// Shift a piece down.  If impossible, returns the piece unchanged.
+    #[must_use]
+    pub fn down(self, board: Board) -> Piece {
+        self.try_down(board).unwrap_or(self)
+    }
+
+    /// Shift a piece left.  If impossible, returns `None`.
+    pub fn try_left(self, board: Board) -> Option<Piece> {
         let mut new = self;
         new.col -= 1;
 
         if (new.col < 0) || new.collides_in(board) {
-            self
+            None
         } else {
-            new
+            Some(new)
         }
     }
 
-    /// Shift a piece right.  If impossible, returns the piece unchanged.
-    #[must_use]
-    pub fn right(self, board: Board) -> Piece {
+    /// Shift a piece right.  If impossible, returns `None`.
+    pub fn try_right(self, board: Board) -> Option<Piece> {
         let mut new = self;
         new.col += 1;
         let max_col = PIECE_MAX_COLS[self.shape as usize][self.orientation as usize];
 
         if (new.col > max_col) || new.collides_in(board) {
-            self
+            None
         } else {
-            new
+            Some(new)
         }
     }
 
-    /// Shift a piece down.  If impossible, returns the piece unchanged.
-    #[must_use]
-    pub fn down(self, board: Board) -> Piece {
+    /// Shift a piece down.  If impossible, returns `None`.
+    pub fn try_down(self, board: Board) -> Option<Piece> {
         let mut new = self;
         new.row -= 1;
 
         if (new.row < 0) || new.collides_in(board) {
-            self
+            None
         } else {
-            new
+            Some(new)
         }
     }
 
@@ -554,6 +1218,188 @@ impl Piece {
 
         self
     }
+
+    /// Rotate a piece 180° according to the given physics.  If impossible,
+    /// returns the piece unchanged.
+    ///
+    /// Under [`Physics::SRS`], half rotations never kick:  the piece either
+    /// turns in place or stays unchanged.
+    #[must_use]
+    pub fn half(self, board: Board, physics: Physics) -> Piece {
+        let orientation = self.orientation.half();
+        let o = self.orientation as usize;
+
+        let kicks: &[(i8, i8)] = match (physics, self.shape) {
+            (Physics::Custom(custom), _) => custom.offsets(self.shape, 1, self.orientation),
+            (_, Shape::O) => &[(0, 0)],
+            (Physics::SRS, _) => &JSTRIS_HALF_KICKS[o][..1],
+            (Physics::Jstris, _) => &JSTRIS_HALF_KICKS[o],
+            (Physics::Tetrio, Shape::I) => &TETRIO_I_HALF_KICKS[o],
+            (Physics::Tetrio, _) => &TETRIO_JLSTZ_HALF_KICKS[o],
+        };
+
+        self.kick(board, orientation, kicks)
+    }
+
+    /// Rotate a piece in the given direction according to the given physics.
+    /// If impossible, returns the piece unchanged.
+    ///
+    /// Quarter rotations are the same as [`cw`] and [`ccw`], except for I
+    /// pieces under [`Physics::Tetrio`], which use SRS+ kicks, and under
+    /// [`Physics::Custom`].  Half rotations are the same as [`half`].
+    ///
+    /// [`cw`]:   Piece::cw
+    /// [`ccw`]:  Piece::ccw
+    /// [`half`]: Piece::half
+    #[must_use]
+    pub fn rotate(self, rotation: Rotation, board: Board, physics: Physics) -> Piece {
+        let o = self.orientation as usize;
+
+        match (rotation, physics, self.shape) {
+            (Rotation::Clockwise, Physics::Custom(custom), _) => self.kick(
+                board,
+                self.orientation.cw(),
+                custom.offsets(self.shape, 0, self.orientation),
+            ),
+            (Rotation::CounterClockwise, Physics::Custom(custom), _) => self.kick(
+                board,
+                self.orientation.ccw(),
+                custom.offsets(self.shape, 2, self.orientation),
+            ),
+            (Rotation::Clockwise, Physics::Tetrio, Shape::I) => {
+                self.kick(board, self.orientation.cw(), &TETRIO_I_CW_KICKS[o])
+            }
+            (Rotation::CounterClockwise, Physics::Tetrio, Shape::I) => {
+                self.kick(board, self.orientation.ccw(), &TETRIO_I_CCW_KICKS[o])
+            }
+            (Rotation::Clockwise, _, _) => self.cw(board),
+            (Rotation::CounterClockwise, _, _) => self.ccw(board),
+            (Rotation::Half, _, _) => self.half(board, physics),
+        }
+    }
+
+    /// Try to move a piece into a new orientation, **adding** each kick
+    /// `(column, row)` to the position in order, until the piece fits.  If it
+    /// never fits, returns the piece unchanged.
+    fn kick(self, board: Board, orientation: Orientation, kicks: &[(i8, i8)]) -> Piece {
+        for (kick_col, kick_row) in kicks {
+            let new = Piece {
+                shape: self.shape,
+                col: self.col + kick_col,
+                row: self.row + kick_row,
+                orientation,
+            };
+
+            if new.in_bounds() && !new.collides_in(board) {
+                return new;
+            }
+        }
+
+        self
+    }
+
+    /// Apply a single move.  If impossible, returns the piece unchanged.
+    #[must_use]
+    pub fn apply(self, m: Move, board: Board, physics: Physics) -> Piece {
+        match m {
+            Move::Left => self.left(board),
+            Move::Right => self.right(board),
+            Move::Down => self.down(board),
+            Move::Rotate(rotation) => self.rotate(rotation, board, physics),
+        }
+    }
+
+    /// Classify a T piece placed into a board (**before** the piece is
+    /// placed) by the three-corner rule.
+    ///
+    /// At least three of the four cells diagonal from the T's center must be
+    /// filled.  Walls and the floor count as filled; rows above the board
+    /// count as empty.  If both corners on the pointed side are filled, it's a
+    /// full T-spin.  Otherwise it's a mini, unless the piece could have
+    /// arrived by the last SRS kick (as in a TST), which upgrades it to full.
+    ///
+    /// Assumes that the last move was a rotation.  Non-T pieces are never
+    /// spins.
+    pub fn spin_kind(self, board: Board) -> SpinKind {
+        if self.shape != Shape::T {
+            return SpinKind::None;
+        }
+
+        let (center_col, center_row) = match self.orientation {
+            Orientation::North => (self.col + 1, self.row),
+            Orientation::East => (self.col, self.row + 1),
+            Orientation::South | Orientation::West => (self.col + 1, self.row + 1),
+        };
+        let (front_col, front_row) = match self.orientation {
+            Orientation::North => (0, 1),
+            Orientation::East => (1, 0),
+            Orientation::South => (0, -1),
+            Orientation::West => (-1, 0),
+        };
+
+        let filled = |col: i8, row: i8| {
+            !(0..10).contains(&col) || row < 0 || (row < 4 && board.get(row, col))
+        };
+
+        let mut corners = 0;
+        let mut front = 0;
+        for (dc, dr) in [(-1, -1), (1, -1), (-1, 1), (1, 1)] {
+            if filled(center_col + dc, center_row + dr) {
+                corners += 1;
+                if dc * front_col + dr * front_row > 0 {
+                    front += 1;
+                }
+            }
+        }
+
+        if corners < 3 {
+            SpinKind::None
+        } else if front == 2 || self.last_kick_reaches(board) {
+            SpinKind::Full
+        } else {
+            SpinKind::Mini
+        }
+    }
+
+    /// Check whether a quarter rotation using the last kick offset could have
+    /// moved a piece into this position.
+    fn last_kick_reaches(self, board: Board) -> bool {
+        let kicks = KICKS[self.shape as usize];
+
+        let from_ccw = self.orientation.ccw();
+        let (kick_col, kick_row) = kicks[from_ccw as usize][4];
+        let cw_source = Piece {
+            col: self.col - kick_col,
+            row: self.row - kick_row,
+            orientation: from_ccw,
+            ..self
+        };
+
+        let (kick_col, kick_row) = kicks[self.orientation as usize][4];
+        let ccw_source = Piece {
+            col: self.col + kick_col,
+            row: self.row + kick_row,
+            orientation: self.orientation.cw(),
+            ..self
+        };
+
+        let fits = |p: Piece| p.in_bounds() && !p.collides_in(board);
+
+        (fits(cw_source) && cw_source.cw(board) == self)
+            || (fits(ccw_source) && ccw_source.ccw(board) == self)
+    }
+}
+
+/// Replay a sequence of moves, starting from the given piece, and return where
+/// the piece ends up.
+///
+/// Moves that are impossible leave the piece where it is, just like in a game.
+/// Comparing the result to an expected placement checks that a path really
+/// reaches it.
+pub fn apply_moves(start: Piece, board: Board, moves: &[Move], physics: Physics) -> Piece {
+    moves
+        .iter()
+        .fold(start, |piece, &m| piece.apply(m, board, physics))
 }
 
 /// The shape of each piece for each orientation, as a bit board.
@@ -696,6 +1542,64 @@ static KICKS: [&[[(i8, i8); 5]; 4]; 7] = [
     &JLSTZ_KICKS, /* Z */
 ];
 
+/// Quarter-rotation kick data for the I piece under TETRIO, which uses SRS+.
+///
+/// Unlike [`KICKS`], counter-clockwise kicks are not mirrors of clockwise kicks,
+/// so there are two tables.  Both are indexed first by **starting**
+/// orientation, then by kick number.  Each kick is **added** to the position.
+#[rustfmt::skip]
+static TETRIO_I_CW_KICKS: [[(i8, i8); 5]; 4] = [
+    [( 2, -2), ( 3, -2), ( 0, -2), ( 0, -3), ( 3,  0)],
+    [(-2,  1), (-3,  1), ( 0,  1), (-3,  3), ( 0,  0)],
+    [( 1, -1), ( 3, -1), ( 0, -1), ( 3,  0), ( 0, -3)],
+    [(-1,  2), ( 0,  2), (-3,  2), ( 0,  0), (-3,  3)],
+];
+
+/// Counter-clockwise partner of [`TETRIO_I_CW_KICKS`].
+#[rustfmt::skip]
+static TETRIO_I_CCW_KICKS: [[(i8, i8); 5]; 4] = [
+    [( 1, -2), ( 0, -2), ( 3, -2), ( 3, -3), ( 0,  0)],
+    [(-2,  2), (-3,  2), ( 0,  2), (-3,  0), ( 0,  3)],
+    [( 2, -1), ( 0, -1), ( 3, -1), ( 0,  0), ( 3, -3)],
+    [(-1,  1), ( 0,  1), (-3,  1), ( 0,  3), (-3,  0)],
+];
+
+/// Half-rotation kick data for I, J, L, S, T, and Z pieces under Jstris.
+///
+/// The first kick turns the piece in place.  SRS uses only that one.
+///
+/// Indexed first by **starting** orientation, then by kick number.  Each kick
+/// is **added** to the position.
+#[rustfmt::skip]
+static JSTRIS_HALF_KICKS: [[(i8, i8); 2]; 4] = [
+    [( 0, -1), ( 0,  0)],
+    [(-1,  0), ( 0,  0)],
+    [( 0,  1), ( 0,  0)],
+    [( 1,  0), ( 0,  0)],
+];
+
+/// Half-rotation kick data for the I piece under TETRIO.
+///
+/// Indexed like [`JSTRIS_HALF_KICKS`].
+#[rustfmt::skip]
+static TETRIO_I_HALF_KICKS: [[(i8, i8); 6]; 4] = [
+    [( 0, -1), ( 0,  0), ( 1,  0), (-1,  0), ( 1, -1), (-1, -1)],
+    [( 0,  1), ( 0,  0), (-1,  0), ( 1,  0), (-1,  1), ( 1,  1)],
+    [(-1,  0), ( 0,  0), ( 0,  2), ( 0,  1), (-1,  2), (-1,  1)],
+    [( 1,  0), ( 0,  0), ( 0,  2), ( 0,  1), ( 1,  2), ( 1,  1)],
+];
+
+/// Half-rotation kick data for the J, L, S, T, and Z pieces under TETRIO.
+///
+/// Indexed like [`JSTRIS_HALF_KICKS`].
+#[rustfmt::skip]
+static TETRIO_JLSTZ_HALF_KICKS: [[(i8, i8); 6]; 4] = [
+    [( 0, -1), ( 0,  0), ( 1,  0), (-1,  0), ( 1, -1), (-1, -1)],
+    [(-1,  0), ( 0,  0), ( 0,  2), ( 0,  1), (-1,  2), (-1,  1)],
+    [( 0,  1), ( 0,  0), (-1,  0), ( 1,  0), (-1,  1), ( 1,  1)],
+    [( 1,  0), ( 0,  0), ( 0,  2), ( 0,  1), ( 1,  2), ( 1,  1)],
+];
+
 /// Bit mask for the bottom four rows (bottom 40 bits) of the game [board].
 ///
 /// [board]: Board
@@ -725,6 +1629,28 @@ impl Shape {
         ["I", "J", "L", "O", "S", "T", "Z"][self as usize]
     }
 
+    /// The shape that looks like this one reflected left-to-right.
+    ///
+    /// J and L swap, and so do S and Z.  The others are symmetric.
+    pub fn mirror(self) -> Shape {
+        match self {
+            Shape::J => Shape::L,
+            Shape::L => Shape::J,
+            Shape::S => Shape::Z,
+            Shape::Z => Shape::S,
+            shape => shape,
+        }
+    }
+
+    /// The usual color of this shape, as a CSS hex color.
+    ///
+    /// These match the colors used by the web solver.
+    pub fn color(self) -> &'static str {
+        [
+            "#41AFDE", "#1883BF", "#EF9536", "#F7D33E", "#66C65C", "#B451AC", "#EF624D",
+        ][self as usize]
+    }
+
     /// Try to convert back from a `u8`.
     pub fn try_from(n: u8) -> Option<Shape> {
         match n {
@@ -774,6 +1700,20 @@ impl Orientation {
         }
     }
 
+    /// The orientation of a piece reflected left-to-right, as its
+    /// [mirrored shape](Shape::mirror).
+    ///
+    /// East and West swap.
+    pub fn mirror(self) -> Orientation {
+        use Orientation::*;
+        match self {
+            North => North,
+            East => West,
+            South => South,
+            West => East,
+        }
+    }
+
     /// A canonical orientation for the given shape, with respect to symmetry.
     pub fn canonical(self, shape: Shape) -> Orientation {
         use Orientation::*;
@@ -807,3 +1747,152 @@ impl Orientation {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::gameplay::{Board, Orientation, Piece, Shape, BOARD_MASK};
+
+    #[test]
+    fn display_rows() {
+        let rows = [
+            "#_________", // row 3, bits 30-39
+            "__________",
+            "__________",
+            "_________#", // row 0, bits 0-9
+        ];
+
+        let board = Board::from_display_rows(rows).unwrap();
+        assert_eq!(board, Board(1 << 30 | 1 << 9));
+        assert_eq!(board.to_display_rows(), rows);
+
+        let board = Board(0b0000000000_1111111111_0101010101_0011001100);
+        let drawn = board.to_display_rows();
+        let drawn = [&*drawn[0], &*drawn[1], &*drawn[2], &*drawn[3]];
+        assert_eq!(Board::from_display_rows(drawn), Some(board));
+
+        assert_eq!(Board::from_display_rows(["", "", "", ""]), None);
+        assert_eq!(
+            Board::from_display_rows(["__IIII____", "__________", "__________", "__________"]),
+            Some(Board(0b0000111100 << 30)),
+        );
+    }
+
+    #[test]
+    fn place_clearing() {
+        let board = Board(0b0000111111_1111110000);
+
+        let i = |col, row| Piece {
+            shape: Shape::I,
+            col,
+            row,
+            orientation: Orientation::North,
+        };
+
+        // Row 1 fills and moves to the bottom.
+        let (placed, cleared) = i(6, 1).place_clearing(board);
+        assert_eq!(placed, Board(0b1111110000_1111111111));
+        assert_eq!(cleared, 0b0010);
+        assert_eq!(placed, i(6, 1).place(board));
+
+        let (placed, cleared) = i(0, 0).place_clearing(board);
+        assert_eq!(placed, Board(0b0000111111_1111111111));
+        assert_eq!(cleared, 0b0001);
+
+        let (_, cleared) = i(6, 0).place_clearing(Board(0));
+        assert_eq!(cleared, 0);
+    }
+
+    #[test]
+    fn mirror() {
+        let board =
+            Board::from_display_rows(["#_________", "##________", "###_#_____", "####_##__#"])
+                .unwrap();
+
+        let mirrored =
+            Board::from_display_rows(["_________#", "________##", "_____#_###", "#__##_####"])
+                .unwrap();
+
+        assert_eq!(board.mirror(), mirrored);
+        assert_eq!(board.mirror().mirror(), board);
+        assert_eq!(board.mirror().0 & !BOARD_MASK, 0);
+        assert_eq!(Board(BOARD_MASK).mirror(), Board(BOARD_MASK));
+    }
+
+    #[test]
+    fn wells() {
+        let board =
+            Board::from_display_rows(["__________", "#_________", "#_##_###_#", "##########"])
+                .unwrap();
+
+        assert_eq!(board.column_heights(), [3, 1, 2, 2, 1, 2, 2, 2, 1, 2]);
+        assert_eq!(board.wells(), 0b0100010010);
+
+        assert_eq!(Board::empty().wells(), 0);
+        assert_eq!(Board(0b0111111111).wells(), 0b1000000000);
+    }
+
+    #[test]
+    fn feature_bytes() {
+        let board =
+            Board::from_display_rows(["__________", "#_________", "#_##_###_#", "##_#######"])
+                .unwrap();
+
+        assert_eq!(
+            board.to_feature_bytes(),
+            [
+                3, 1, 2, 2, 1, 2, 2, 2, 1, 2, // heights
+                0, 0, 1, 0, 0, 0, 0, 0, 0, 0, // holes
+                7, // bumpiness
+            ]
+        );
+        assert_eq!(Board::empty().to_feature_bytes(), [0; 21]);
+    }
+
+    #[test]
+    fn independent_regions() {
+        let board =
+            Board::from_display_rows(["#___#_____", "##__#_____", "##__##____", "###_###___"])
+                .unwrap();
+
+        let regions: Vec<_> = board
+            .independent_regions()
+            .into_iter()
+            .map(|(region, columns)| (region.to_display_rows(), columns))
+            .collect();
+
+        assert_eq!(regions.len(), 2);
+        assert_eq!(
+            regions[0],
+            (
+                [
+                    "#___######".to_owned(),
+                    "##__######".to_owned(),
+                    "##__######".to_owned(),
+                    "###_######".to_owned(),
+                ],
+                0b0000001110
+            )
+        );
+        assert_eq!(regions[1].1, 0b1111100000);
+        assert_eq!(regions[1].0[0], "#####_____");
+
+        assert_eq!(
+            Board::empty().independent_regions(),
+            [(Board::empty(), 0b1111111111)]
+        );
+        assert_eq!(Board(BOARD_MASK).independent_regions(), []);
+    }
+
+    #[test]
+    fn surface() {
+        let board =
+            Board::from_display_rows(["__________", "#_________", "#_##_###_#", "##_#######"])
+                .unwrap();
+
+        let surface =
+            Board::from_display_rows(["__________", "#_________", "__##_###_#", "_#__#___#_"]);
+
+        assert_eq!(Some(board.surface()), surface);
+        assert_eq!(Board::empty().surface(), Board::empty());
+    }
+}
@@ -1,4 +1,6 @@
-use std::{borrow::Borrow, collections::BTreeSet, iter::FromIterator};
+use std::{
+    borrow::Borrow, collections::BTreeSet, convert::TryFrom, iter::FromIterator, sync::OnceLock,
+};
 
 use crate::gameplay::Shape;
 
@@ -53,6 +55,17 @@ impl Queue {
         (highest_one + 2) / 3
     }
 
+    /// Slide a window over a long sequence of shapes, producing every
+    /// contiguous queue of the given size, in order.
+    ///
+    /// Requires that 0 &lt; `size` &le; 10.
+    pub fn windows(shapes: &[Shape], size: usize) -> impl Iterator<Item = Queue> + '_ {
+        assert!(size > 0);
+        assert!(size <= 10);
+
+        shapes.windows(size).map(|window| window.iter().collect())
+    }
+
     /// Produce a [`String`] containing the names of the shapes in this queue.
     pub fn to_string(self) -> String {
         let mut s = String::with_capacity(10);
@@ -137,6 +150,66 @@ impl Queue {
         results
     }
 
+    /// Every ordering of `n` different shapes drawn from one 7-bag, like the
+    /// pattern `*p7` for `n` = 7.
+    ///
+    /// The queues are sorted by [`natural_order_key`](Queue::natural_order_key).
+    /// They're built once and shared after that.
+    ///
+    /// Requires that `n` &le; 7.
+    pub fn full_bag(n: usize) -> &'static [Queue] {
+        static FULL_BAGS: OnceLock<Vec<Vec<Queue>>> = OnceLock::new();
+
+        fn orderings(prefix: Queue, remaining: u8, n: usize, out: &mut Vec<Queue>) {
+            if prefix.len() as usize == n {
+                out.push(prefix);
+                return;
+            }
+
+            for shape in Shape::ALL {
+                if remaining & shape.bit_mask() != 0 {
+                    let rest = remaining & !shape.bit_mask();
+                    orderings(prefix.push_last(shape), rest, n, out);
+                }
+            }
+        }
+
+        assert!(n <= 7);
+
+        let full_bags = FULL_BAGS.get_or_init(|| {
+            (0..=7)
+                .map(|n| {
+                    let mut queues = Vec::new();
+                    orderings(Queue::empty(), 0b1111111, n, &mut queues);
+                    queues.sort_unstable_by_key(|q| q.natural_order_key());
+                    queues
+                })
+                .collect()
+        });
+
+        &full_bags[n]
+    }
+
+    /// Count how many queues there are of each length.
+    ///
+    /// Returns `(length, count)` pairs in increasing order of length, skipping
+    /// lengths with no queues.  This is handy for checking that a pattern
+    /// expanded into the expected mix of queue lengths.
+    pub fn length_histogram(queues: &[Queue]) -> Vec<(usize, usize)> {
+        let mut counts = [0; 11];
+
+        for &queue in queues {
+            counts[queue.len() as usize] += 1;
+        }
+
+        counts
+            .iter()
+            .enumerate()
+            .filter(|&(_, &count)| count != 0)
+            .map(|(len, &count)| (len, count))
+            .collect()
+    }
+
     pub fn natural_order_key(self) -> u32 {
         #![allow(non_snake_case)]
 
@@ -165,6 +238,36 @@ impl Queue {
         let x = self.natural_order_key();
         Queue(x >> (x.trailing_zeros() / 3 * 3))
     }
+
+    /// Replace each shape with its [mirror image](Shape::mirror), keeping the
+    /// order.
+    ///
+    /// Use this along with [`BrokenBoard::mirror`]: a mirrored board needs
+    /// mirrored queues.  The kicks of some rotation systems aren't exactly
+    /// symmetric, though, so to be sure, search the mirrored board again.
+    ///
+    /// [`BrokenBoard::mirror`]: crate::brokenboard::BrokenBoard::mirror
+    #[must_use]
+    pub fn mirror_shapes(self) -> Queue {
+        self.map(Shape::mirror).collect()
+    }
+
+    /// Move the first `n` shapes to the end of the queue, keeping their order.
+    ///
+    /// `n` wraps around the length of the queue, so every `n` gives one of the
+    /// queue's cyclic shifts.
+    #[must_use]
+    pub fn rotate_left(self, n: u32) -> Queue {
+        let len = self.len();
+        if len == 0 {
+            return self;
+        }
+
+        let n = n % len;
+        let first = self.0 & ((1 << (n * 3)) - 1);
+        let rest = self.0 >> (n * 3);
+        Queue(rest | first << ((len - n) * 3))
+    }
 }
 
 impl Iterator for Queue {
@@ -209,9 +312,57 @@ impl<S: Borrow<Shape>> FromIterator<S> for Queue {
     }
 }
 
+/// Error from converting a list of shapes into a [`Queue`]: there were more
+/// than 10 shapes.
+///
+/// Contains the number of shapes.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub struct QueueTooLongError(pub usize);
+
+impl std::fmt::Display for QueueTooLongError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} shapes is too many for a queue (at most 10)", self.0)
+    }
+}
+
+impl std::error::Error for QueueTooLongError {}
+
+/// Unlike [`collect`](Iterator::collect), which keeps the first 10 shapes,
+/// this refuses to drop any shapes.
+impl TryFrom<&[Shape]> for Queue {
+    type Error = QueueTooLongError;
+
+    fn try_from(shapes: &[Shape]) -> Result<Queue, QueueTooLongError> {
+        if shapes.len() > 10 {
+            return Err(QueueTooLongError(shapes.len()));
+        }
+
+        Ok(shapes.iter().collect())
+    }
+}
+
+impl TryFrom<Vec<Shape>> for Queue {
+    type Error = QueueTooLongError;
+
+    fn try_from(shapes: Vec<Shape>) -> Result<Queue, QueueTooLongError> {
+        Queue::try_from(shapes.as_slice())
+    }
+}
+
+impl From<Queue> for Vec<Shape> {
+    fn from(queue: Queue) -> Vec<Shape> {
+        queue.collect()
+    }
+}
+
 #[cfg(test)]
 mod tests {
-    use crate::{gameplay::Shape, queue::Queue};
+    use std::convert::TryFrom;
+
+    use crate::{
+        gameplay::Shape,
+        queue::{Queue, QueueTooLongError},
+    };
 
     #[test]
     fn order() {
@@ -260,6 +411,58 @@ mod tests {
         );
     }
 
+    #[test]
+    fn rotate_left() {
+        use Shape::*;
+
+        let queue: Queue = [I, J, L, O].iter().collect();
+
+        assert_eq!(queue.rotate_left(0), queue);
+        assert_eq!(queue.rotate_left(1), [J, L, O, I].iter().collect());
+        assert_eq!(queue.rotate_left(3), [O, I, J, L].iter().collect());
+        assert_eq!(queue.rotate_left(5), queue.rotate_left(1));
+        assert_eq!(Queue::empty().rotate_left(2), Queue::empty());
+
+        let full: Queue = [I, J, L, O, S, T, Z, I, J, L].iter().collect();
+        assert_eq!(
+            full.rotate_left(9),
+            [L, I, J, L, O, S, T, Z, I, J].iter().collect()
+        );
+    }
+
+    #[test]
+    fn convert() {
+        use Shape::*;
+
+        let shapes = vec![I, J, L, O, S, T, Z, I, J, L];
+        let queue = Queue::try_from(shapes.clone()).unwrap();
+
+        assert_eq!(queue, shapes.iter().collect());
+        assert_eq!(Vec::from(queue), shapes);
+        assert_eq!(Vec::from(Queue::empty()), vec![]);
+
+        let mut too_long = shapes;
+        too_long.push(T);
+        assert_eq!(Queue::try_from(too_long), Err(QueueTooLongError(11)));
+    }
+
+    #[test]
+    fn windows() {
+        use Shape::*;
+
+        let shapes = [I, J, L, O, S];
+        let windows: Vec<Queue> = Queue::windows(&shapes, 3).collect();
+
+        let expected: &[Queue] = &[
+            [I, J, L].iter().collect(),
+            [J, L, O].iter().collect(),
+            [L, O, S].iter().collect(),
+        ];
+
+        assert_eq!(windows, expected);
+        assert_eq!(Queue::windows(&shapes, 6).count(), 0);
+    }
+
     #[test]
     #[rustfmt::skip]
     fn unhold_many() {
@@ -295,4 +498,21 @@ mod tests {
                 &[I, I, I, T],
             ], 1 + 2 + 3 + 4);
     }
+
+    #[test]
+    fn full_bag() {
+        let counts: Vec<usize> = (0..=7).map(|n| Queue::full_bag(n).len()).collect();
+        assert_eq!(counts, [1, 7, 42, 210, 840, 2520, 5040, 5040]);
+
+        for n in 0..=7 {
+            let queues = Queue::full_bag(n);
+            assert!(queues.iter().all(|queue| queue.len() as usize == n));
+            assert!(queues
+                .windows(2)
+                .all(|pair| pair[0].natural_order_key() < pair[1].natural_order_key()));
+        }
+
+        assert_eq!(Queue::full_bag(7)[0].to_string(), "IJLOSTZ");
+        assert_eq!(Queue::full_bag(7)[5039].to_string(), "ZTSOLJI");
+    }
 }
@@ -76,10 +76,11 @@
 //! keep track using this method --- all possible positions are considered
 //! equal.
 //!
-//! Even though this method is possible to adapt for boards larger than 4 lines,
-//! doing so is even trickier than writing this module.  And this module was
-//! pretty tricky already.  It's very difficult to check whether code like this
-//! is working the way you expect.
+//! The working area is 6 rows, so boards up to 5 lines tall fit (see
+//! [`Placements::place_in`]), with at least one spawn row above.  Adapting
+//! this method for boards taller than that is even trickier than writing this
+//! module.  And this module was pretty tricky already.  It's very difficult to
+//! check whether code like this is working the way you expect.
 //!
 //! ## Does it work?
 //!
@@ -91,6 +92,8 @@
 //! [`gameplay`]:     crate::gameplay
 //! [`piece_placer`]: crate::piece_placer
 
+use std::{collections::HashSet, sync::Mutex};
+
 use crate::gameplay::{Board, Orientation, Physics, Piece, Shape};
 
 /// Vector of positions on a board.
@@ -142,18 +145,42 @@ impl Placements {
     ///
     /// See [`PlacementMachine`] for details.
     pub fn place(board: Board, shape: Shape, physics: Physics) -> Self {
+        Placements::place_in(board, shape, physics, 4)
+    }
+
+    /// Find all placeable positions of the given shape on a board `height`
+    /// rows tall, instead of 4.
+    ///
+    /// `board` may use the bottom `10 * height` bits.  Pieces spawn in the
+    /// rows above the board (see [`spawn`]), and positions are only placeable
+    /// if the whole piece is in the bottom `height` rows.
+    ///
+    /// Iterating over the result places each piece with [`Piece::place`],
+    /// which only knows about 4 rows.  For a taller board, use
+    /// [`iter_placeable_pieces`](Placements::iter_placeable_pieces) or
+    /// [`contains`](Placements::contains) instead.
+    ///
+    /// Requires that 1 &le; `height` &le; 5, so that there is at least one
+    /// spawn row in the 6-row working area.  Kicks which would move a piece
+    /// above the working area are lost, so on a 5-row board, a piece can
+    /// only spawn in one row, and some upward kicks near the top of the board
+    /// are missed.
+    pub fn place_in(board: Board, shape: Shape, physics: Physics, height: u8) -> Self {
         use Orientation::*;
 
+        assert!((1..=5).contains(&height));
+
         let collision = &COLLISION[shape as usize];
+        let spawn = spawn(height);
 
-        if shape == Shape::O {
+        if shape == Shape::O && !physics.o_can_kick() {
             // Shortcut for O.
             // - An O piece can never move upwards (no up-kicks)
             // - All O orientations are completely identical
 
             let viable = collision[0].viable(board);
-            let reachable = (SPAWN & viable).flood_fill(viable);
-            let placeable = collision[0].placeable(reachable);
+            let reachable = (spawn & viable).flood_fill(viable);
+            let placeable = collision[0].placeable_in(reachable, height);
 
             return Placements {
                 shape,
@@ -162,42 +189,110 @@ impl Placements {
             };
         }
 
+        let mut machine = PlacementMachine::with_spawn(
+            [
+                collision[0].viable(board),
+                collision[1].viable(board),
+                collision[2].viable(board),
+                collision[3].viable(board),
+            ],
+            spawn,
+        );
+
+        // Only reaches O kicks if O pieces can kick; otherwise, see the
+        // shortcut above.
+        machine.run_physics(physics, shape);
+
+        let placeable = |o: Orientation| {
+            collision[o as usize].placeable_in(machine.reachable[o as usize], height)
+        };
+
+        Placements {
+            shape,
+            board,
+            positions: [
+                placeable(North),
+                placeable(East),
+                placeable(South),
+                placeable(West),
+            ],
+        }
+    }
+
+    /// Find all placeable positions of the given shape on each of the given
+    /// boards.
+    ///
+    /// The result is the same as calling [`place`](Placements::place) on each
+    /// board, in order.
+    ///
+    /// When O pieces can't kick, their search is a single flood fill, so O
+    /// placements are found four boards at a time with
+    /// [`PVec::flood_fill_x4`].  Other shapes are searched one board at a
+    /// time.  Running four [`PlacementMachine`]s in lockstep was tried, and it
+    /// was slower:  each machine needs only a few short flood fills, and the
+    /// lanes spend most of their time waiting for the slowest board.  The
+    /// ignored `place_many_timing` test compares this with `place`.
+    pub fn place_many(boards: &[Board], shape: Shape, physics: Physics) -> Vec<Self> {
+        if shape != Shape::O || physics.o_can_kick() {
+            return boards
+                .iter()
+                .map(|&board| Placements::place(board, shape, physics))
+                .collect();
+        }
+
+        let collision = &COLLISION[shape as usize];
+        let mut placements = Vec::with_capacity(boards.len());
+
+        for chunk in boards.chunks(4) {
+            // Pad the last chunk by repeating its last board.
+            let mut batch = [*chunk.last().unwrap(); 4];
+            batch[..chunk.len()].copy_from_slice(chunk);
+
+            // Same shortcut as in `place`.
+            let viable = batch.map(|board| collision[0].viable(board));
+            let reachable = PVec::flood_fill_x4(viable.map(|v| SPAWN & v), viable);
+
+            for (&board, reachable) in chunk.iter().zip(reachable) {
+                placements.push(Placements {
+                    shape,
+                    board,
+                    positions: [collision[0].placeable(reachable); 4],
+                });
+            }
+        }
+
+        placements
+    }
+
+    /// Find all placeable positions of the given shape on the given board, in
+    /// a game without soft drop.
+    ///
+    /// The piece can move and rotate only while it's entirely above the board,
+    /// in the [`SPAWN`] rows.  Then it hard drops straight down.  So there are
+    /// no tucks, spins, or other maneuvers under the stack.
+    pub fn hard_drop_only(board: Board, shape: Shape, physics: Physics) -> Self {
+        let collision = &COLLISION[shape as usize];
+
         let viable = [
             collision[0].viable(board),
             collision[1].viable(board),
             collision[2].viable(board),
             collision[3].viable(board),
         ];
-        let reachable = [
-            SPAWN & viable[0],
-            SPAWN & viable[1],
-            SPAWN & viable[2],
-            SPAWN & viable[3],
-        ];
-        let mut machine = PlacementMachine {
-            viable,
-            reachable,
-            dirty: [true; 4],
-            shape,
-            physics,
-        };
 
-        while machine.any_dirty() {
-            machine.step(North);
-            machine.step(East);
-            machine.step(South);
-            machine.step(West);
+        let mut machine = PlacementMachine::new(viable.map(|v| v & SPAWN));
+        machine.run_physics(physics, shape);
+
+        let mut positions = [PVec(0); 4];
+        for o in 0..4 {
+            let dropped = machine.reachable[o].drop_fill(viable[o]);
+            positions[o] = collision[o].placeable(dropped);
         }
 
         Placements {
             shape,
             board,
-            positions: [
-                machine.placeable(North),
-                machine.placeable(East),
-                machine.placeable(South),
-                machine.placeable(West),
-            ],
+            positions,
         }
     }
 
@@ -211,7 +306,12 @@ impl Placements {
         match self.shape {
             O => Placements {
                 // 90° symmetry, all orientations identical
-                positions: [self.positions[0], PVec(0), PVec(0), PVec(0)],
+                positions: [
+                    self.positions[0] | self.positions[1] | self.positions[2] | self.positions[3],
+                    PVec(0),
+                    PVec(0),
+                    PVec(0),
+                ],
                 ..self
             },
 
@@ -237,20 +337,282 @@ impl Placements {
             && self.positions[piece.orientation as usize].contains(piece.col, piece.row)
     }
 
+    /// Split two sets of placements into the pieces found only in `self` and
+    /// the pieces found only in `other`.
+    ///
+    /// Useful for comparing physics:  for example, the SRS and TETRIO
+    /// placements of the same shape on the same board.  Both sets must have
+    /// the same shape and board.  If only one of them is
+    /// [`canonical`](Placements::canonical), make both canonical first, or
+    /// equivalent pieces will show up as different.
+    pub fn symmetric_difference(&self, other: &Placements) -> (Placements, Placements) {
+        assert_eq!(self.shape, other.shape);
+        assert_eq!(self.board, other.board);
+
+        let only = |a: &Placements, b: &Placements| Placements {
+            positions: [0, 1, 2, 3].map(|o| PVec(a.positions[o].0 & !b.positions[o].0)),
+            ..a.clone()
+        };
+
+        (only(self, other), only(other, self))
+    }
+
+    /// Count the fewest rotations needed to reach the given piece, starting
+    /// from spawn facing north.
+    ///
+    /// Moving and dropping are free.  Kicks are followed as usual, and a half
+    /// rotation counts as one.  Orientations that look the same (see
+    /// [`canonical`](Placements::canonical)) are interchangeable, so an O piece
+    /// never needs to rotate.
+    ///
+    /// `physics` must be the physics this set was found with.  Returns `None`
+    /// if the piece isn't in this set.
+    pub fn rotation_cost(&self, piece: Piece, physics: Physics) -> Option<u8> {
+        use Orientation::*;
+
+        if !self.contains(piece) {
+            return None;
+        }
+
+        let collision = &COLLISION[self.shape as usize];
+        let viable = [0, 1, 2, 3].map(|o| collision[o].viable(self.board));
+        let kicks = kick_table(physics, self.shape);
+
+        let target = piece.orientation.canonical(self.shape);
+        let found = |reachable: &[PVec; 4]| {
+            [North, East, South, West].iter().any(|&o| {
+                o.canonical(self.shape) == target
+                    && collision[o as usize]
+                        .placeable(reachable[o as usize])
+                        .contains(piece.col, piece.row)
+            })
+        };
+
+        let mut reachable = [PVec(0); 4];
+        reachable[North as usize] = (SPAWN & viable[North as usize]).flood_fill(viable[0]);
+
+        for cost in 0.. {
+            if found(&reachable) {
+                return Some(cost);
+            }
+
+            let mut next = reachable;
+            for o in [North, East, South, West] {
+                let from = reachable[o as usize];
+                next[o.cw() as usize] |= kicks.cw(o, from, viable[o.cw() as usize]);
+                next[o.half() as usize] |= kicks.half(o, from, viable[o.half() as usize]);
+                next[o.ccw() as usize] |= kicks.ccw(o, from, viable[o.ccw() as usize]);
+            }
+            for o in 0..4 {
+                next[o] = next[o].flood_fill(viable[o]);
+            }
+
+            if next == reachable {
+                break;
+            }
+            reachable = next;
+        }
+
+        None
+    }
+
     /// Remove the given piece from this set of placements.  Returns true if the
     /// piece was initially present, or false if it wasn't.
     pub fn remove(&mut self, piece: Piece) -> bool {
         self.shape == piece.shape
             && self.positions[piece.orientation as usize].remove(piece.col, piece.row)
     }
+
+    /// Drain this set of placements as bare pieces, in the same order as
+    /// iterating, but without computing the board after each placement.
+    ///
+    /// Unlike iterating, this also works for placements from
+    /// [`place_in`](Placements::place_in) with a height above four.
+    pub fn iter_placeable_pieces(mut self) -> impl Iterator<Item = Piece> {
+        std::iter::from_fn(move || self.next_piece())
+    }
+
+    /// Remove and return the first piece in iteration order.
+    fn next_piece(&mut self) -> Option<Piece> {
+        use Orientation::*;
+
+        for orientation in [North, East, South, West] {
+            let positions = &mut self.positions[orientation as usize];
+
+            if positions.0 != 0 {
+                let cell = positions.0.trailing_zeros() as i8;
+                let col = cell % 10;
+                let row = cell / 10;
+
+                positions.0 ^= 1 << cell;
+
+                return Some(Piece {
+                    shape: self.shape,
+                    col,
+                    row,
+                    orientation,
+                });
+            }
+        }
+
+        None
+    }
+
+    /// Drain this set of placements in reading order, for listing in a user
+    /// interface.
+    ///
+    /// Pieces are sorted left to right by column, then top to bottom by row.
+    /// Pieces in the same position keep their usual order by orientation.
+    pub fn into_reading_order(self) -> Vec<(Piece, Board)> {
+        let mut placements: Vec<(Piece, Board)> = self.collect();
+        placements.sort_by_key(|(piece, _)| (piece.col, std::cmp::Reverse(piece.row)));
+        placements
+    }
+
+    /// Find which orientations in this set merge into the given piece under
+    /// [`canonical`](Placements::canonical).
+    ///
+    /// This set should be the raw placements, before `canonical`.  The piece
+    /// should be in its canonical orientation, like the pieces from
+    /// `canonical` or in a [`BrokenPiece`](crate::brokenboard::BrokenPiece).
+    /// The result is empty if no orientation places the piece there.
+    pub fn origin_orientations(&self, piece: Piece) -> Vec<Orientation> {
+        use Orientation::*;
+
+        if piece.shape != self.shape || piece.col < 0 || piece.row < 0 {
+            return Vec::new();
+        }
+
+        let bit = 1 << (piece.col + piece.row * 10);
+
+        [North, East, South, West]
+            .iter()
+            .copied()
+            .filter(|&o| o.canonical(self.shape) == piece.orientation)
+            .filter(|&o| self.positions[o as usize].0 & bit != 0)
+            .collect()
+    }
+
+    /// Find every cell that some piece in this set would occupy.
+    ///
+    /// This is the same as combining [`Piece::as_board`] for each piece, but
+    /// it's computed directly from the position vectors.
+    pub fn coverage(&self) -> Board {
+        let mut cells = 0;
+
+        for (collision, positions) in COLLISION[self.shape as usize].iter().zip(self.positions) {
+            for shift in collision.shifts {
+                cells |= positions.0 << shift;
+            }
+        }
+
+        Board(cells & ((1 << 40) - 1))
+    }
+}
+
+/// Find all placeable positions of every shape on the given board.
+///
+/// The result is indexed by [shape](Shape).  It's the same as calling
+/// [`Placements::place`] once for each shape.
+pub fn bag_placements(board: Board, physics: Physics) -> [Placements; 7] {
+    Shape::ALL.map(|shape| Placements::place(board, shape, physics))
+}
+
+/// Count, for each cell, how many placeable pieces would cover it.
+///
+/// Every shape is counted, and pieces which look the same are only counted
+/// once (see [`Placements::canonical`]).  The result is indexed like the bits
+/// of a [`Board`].  Filled cells are always zero.
+pub fn cell_placement_density(board: Board, physics: Physics) -> [u16; 40] {
+    let mut density = [0; 40];
+
+    for placements in bag_placements(board, physics) {
+        for (piece, _) in placements.canonical() {
+            let mut cells = piece.as_board().0;
+
+            while cells != 0 {
+                density[cells.trailing_zeros() as usize] += 1;
+                cells &= cells - 1;
+            }
+        }
+    }
+
+    density
+}
+
+/// Find every board reachable from `start` by placing exactly `piece_count`
+/// pieces, where each piece can be any of the given shapes.
+///
+/// The shapes form a pool, not a queue:  Any shape can be used any number of
+/// times, in any order.  This is for practice scenarios like "only L, S, and T".
+pub fn reachable_boards(
+    start: Board,
+    piece_count: usize,
+    shapes: &[Shape],
+    physics: Physics,
+) -> HashSet<Board> {
+    let mut boards = HashSet::new();
+    boards.insert(start);
+
+    for _ in 0..piece_count {
+        let mut next = HashSet::new();
+
+        for &board in &boards {
+            for &shape in shapes {
+                next.extend(
+                    Placements::place(board, shape, physics)
+                        .canonical()
+                        .map(|(_, new_board)| new_board),
+                );
+            }
+        }
+
+        boards = next;
+    }
+
+    boards
+}
+
+/// Find every board in `legal` which is reachable from `start` by placing at
+/// most `max_pieces` pieces of any shapes.
+///
+/// Only legal boards are followed, so every board along the way is legal too.
+/// `start` is included if it is legal itself.  This is the part of `legal`
+/// that a solver starting from `start` could possibly visit.
+pub fn reachable_legal(
+    start: Board,
+    legal: &HashSet<Board>,
+    max_pieces: usize,
+    physics: Physics,
+) -> HashSet<Board> {
+    let mut found = HashSet::new();
+    if legal.contains(&start) {
+        found.insert(start);
+    }
+
+    let mut frontier = vec![start];
+
+    for _ in 0..max_pieces {
+        let mut next = Vec::new();
+
+        for board in frontier {
+            for placements in bag_placements(board, physics) {
+                for (_, new_board) in placements.canonical() {
+                    if legal.contains(&new_board) && found.insert(new_board) {
+                        next.push(new_board);
+                    }
+                }
+            }
+        }
+
+        frontier = next;
+    }
+
+    found
 }
 
 /// The core of the vectorized algorithm.  Not intended for public use.
 pub struct PlacementMachine {
-    /// Shape of the pieces being placed.  **Constant** during iteration.
-    shape: Shape,
-    /// Physics for half rotations.  **Constant** during iteration.
-    physics: Physics,
     /// Set of viable positions, indexed by orientation.  **Constant** during iteration.
     viable: [PVec; 4],
     /// Set of reachable positions, indexed by orientation.  **Variable** during iteration.
@@ -259,7 +621,86 @@ pub struct PlacementMachine {
     dirty: [bool; 4],
 }
 
+/// The kicks for the given physics and shape, like
+/// [`PlacementMachine::run_physics`] uses, for searches which don't need the
+/// speed of a concrete type.
+fn kick_table(physics: Physics, shape: Shape) -> &'static dyn KickTable {
+    match (physics, shape) {
+        (Physics::Custom(custom), _) => &custom.kicks[shape as usize],
+        (_, Shape::O) => &SRS_O,
+
+        (Physics::SRS, Shape::I) => &SRS_I,
+        (Physics::SRS, _) => &SRS_JLSTZ,
+
+        (Physics::Jstris, Shape::I) => &JSTRIS_I,
+        (Physics::Jstris, _) => &JSTRIS_JLSTZ,
+
+        (Physics::Tetrio, Shape::I) => &TETRIO_I,
+        (Physics::Tetrio, _) => &TETRIO_JLSTZ,
+    }
+}
+
+/// Kick data which can rotate position vectors.
+///
+/// This is implemented by the static [`Kicks`] tables and by [`BoxedKicks`],
+/// so that [`PlacementMachine`] can run with either.
+pub trait KickTable {
+    fn cw(&self, initial: Orientation, from: PVec, viable: PVec) -> PVec;
+    fn half(&self, initial: Orientation, from: PVec, viable: PVec) -> PVec;
+    fn ccw(&self, initial: Orientation, from: PVec, viable: PVec) -> PVec;
+}
+
 impl PlacementMachine {
+    /// Start a search from the given viable positions.  Initially, the only
+    /// reachable positions are the viable [`SPAWN`] positions.
+    fn new(viable: [PVec; 4]) -> PlacementMachine {
+        PlacementMachine::with_spawn(viable, SPAWN)
+    }
+
+    /// Start a search like [`new`](PlacementMachine::new), but from the
+    /// viable positions in `spawn` instead.
+    fn with_spawn(viable: [PVec; 4], spawn: PVec) -> PlacementMachine {
+        PlacementMachine {
+            viable,
+            reachable: [
+                spawn & viable[0],
+                spawn & viable[1],
+                spawn & viable[2],
+                spawn & viable[3],
+            ],
+            dirty: [true; 4],
+        }
+    }
+
+    /// Visit every orientation until no new reachable positions are found.
+    fn run(&mut self, kicks: &impl KickTable) {
+        use Orientation::*;
+
+        while self.any_dirty() {
+            self.step(North, kicks);
+            self.step(East, kicks);
+            self.step(South, kicks);
+            self.step(West, kicks);
+        }
+    }
+
+    /// Run with the kicks for the given physics and shape.
+    fn run_physics(&mut self, physics: Physics, shape: Shape) {
+        match (physics, shape) {
+            (Physics::Custom(custom), _) => self.run(&custom.kicks[shape as usize]),
+            (_, Shape::O) => self.run(&SRS_O),
+
+            (Physics::SRS, Shape::I) => self.run(&SRS_I),
+            (Physics::SRS, _) => self.run(&SRS_JLSTZ),
+
+            (Physics::Jstris, Shape::I) => self.run(&JSTRIS_I),
+            (Physics::Jstris, _) => self.run(&JSTRIS_JLSTZ),
+
+            (Physics::Tetrio, Shape::I) => self.run(&TETRIO_I),
+            (Physics::Tetrio, _) => self.run(&TETRIO_JLSTZ),
+        }
+    }
+
     /// Check whether any reachable sets need to be visited.  If false,
     /// iteration is complete.
     fn any_dirty(&self) -> bool {
@@ -273,7 +714,7 @@ impl PlacementMachine {
     ///
     /// [flood fills]: PVec::flood_fill
     /// [kicks]:       Kicks
-    fn step(&mut self, o: Orientation) {
+    fn step(&mut self, o: Orientation, kicks: &impl KickTable) {
         let o_0 = o as usize;
         let o_90 = o.cw() as usize;
         let o_180 = o.half() as usize;
@@ -282,44 +723,9 @@ impl PlacementMachine {
         if self.dirty[o_0] {
             self.reachable[o_0] = self.reachable[o_0].flood_fill(self.viable[o_0]);
 
-            let (more_90, more_180, more_270) = match (self.physics, self.shape) {
-                // O pieces are handled in the shortcut in `Placements::place`.
-                /* (_, Shape::O) => (PVec(0), PVec(0), PVec(0)), */
-                (_, Shape::O) => unreachable!(),
-
-                (Physics::SRS, Shape::I) => (
-                    SRS_I.cw(o, self.reachable[o_0], self.viable[o_90]),
-                    SRS_I.half(o, self.reachable[o_0], self.viable[o_180]),
-                    SRS_I.ccw(o, self.reachable[o_0], self.viable[o_270]),
-                ),
-                (Physics::SRS, _) => (
-                    SRS_JLSTZ.cw(o, self.reachable[o_0], self.viable[o_90]),
-                    SRS_JLSTZ.half(o, self.reachable[o_0], self.viable[o_180]),
-                    SRS_JLSTZ.ccw(o, self.reachable[o_0], self.viable[o_270]),
-                ),
-
-                (Physics::Jstris, Shape::I) => (
-                    JSTRIS_I.cw(o, self.reachable[o_0], self.viable[o_90]),
-                    JSTRIS_I.half(o, self.reachable[o_0], self.viable[o_180]),
-                    JSTRIS_I.ccw(o, self.reachable[o_0], self.viable[o_270]),
-                ),
-                (Physics::Jstris, _) => (
-                    JSTRIS_JLSTZ.cw(o, self.reachable[o_0], self.viable[o_90]),
-                    JSTRIS_JLSTZ.half(o, self.reachable[o_0], self.viable[o_180]),
-                    JSTRIS_JLSTZ.ccw(o, self.reachable[o_0], self.viable[o_270]),
-                ),
-
-                (Physics::Tetrio, Shape::I) => (
-                    TETRIO_I.cw(o, self.reachable[o_0], self.viable[o_90]),
-                    TETRIO_I.half(o, self.reachable[o_0], self.viable[o_180]),
-                    TETRIO_I.ccw(o, self.reachable[o_0], self.viable[o_270]),
-                ),
-                (Physics::Tetrio, _) => (
-                    TETRIO_JLSTZ.cw(o, self.reachable[o_0], self.viable[o_90]),
-                    TETRIO_JLSTZ.half(o, self.reachable[o_0], self.viable[o_180]),
-                    TETRIO_JLSTZ.ccw(o, self.reachable[o_0], self.viable[o_270]),
-                ),
-            };
+            let more_90 = kicks.cw(o, self.reachable[o_0], self.viable[o_90]);
+            let more_180 = kicks.half(o, self.reachable[o_0], self.viable[o_180]);
+            let more_270 = kicks.ccw(o, self.reachable[o_0], self.viable[o_270]);
 
             if (self.reachable[o_90] & more_90) != more_90 {
                 self.reachable[o_90] |= more_90;
@@ -339,13 +745,6 @@ impl PlacementMachine {
             self.dirty[o_0] = false;
         }
     }
-
-    /// After iteration, finds [placeable] positions from reachable positions.
-    ///
-    /// [placeable]: Collision::placeable
-    fn placeable(&self, o: Orientation) -> PVec {
-        COLLISION[self.shape as usize][o as usize].placeable(self.reachable[o as usize])
-    }
 }
 
 impl Iterator for Placements {
@@ -354,30 +753,8 @@ impl Iterator for Placements {
     /// Iterate through orientations clockwise starting from north, least
     /// significant bit (lowest mino) first.
     fn next(&mut self) -> Option<Self::Item> {
-        use Orientation::*;
-
-        for orientation in [North, East, South, West] {
-            let positions = &mut self.positions[orientation as usize];
-
-            if positions.0 != 0 {
-                let cell = positions.0.trailing_zeros() as i8;
-                let col = cell % 10;
-                let row = cell / 10;
-
-                positions.0 ^= 1 << cell;
-
-                let piece = Piece {
-                    shape: self.shape,
-                    col,
-                    row,
-                    orientation,
-                };
-
-                return Some((piece, piece.place(self.board)));
-            }
-        }
-
-        None
+        self.next_piece()
+            .map(|piece| (piece, piece.place(self.board)))
     }
 
     /// Count the number of positions in this set.  This is fast.
@@ -433,7 +810,15 @@ pub const LEFT_50: PVec = PVec(replicate_row(0b0111111111));
 pub const RIGHT_50: PVec = PVec(replicate_row(0b1111111110));
 
 /// Spawn positions: all of the fifth and sixth rows.
-pub const SPAWN: PVec = PVec((FULL_10 << 50) | (FULL_10 << 40));
+pub const SPAWN: PVec = spawn(4);
+
+/// Spawn positions for a board `height` rows tall:  every row above the board,
+/// up to the top of the 6-row working area.
+///
+/// A piece positioned in these rows is entirely above the board.
+pub const fn spawn(height: u8) -> PVec {
+    PVec(FULL_60 & !((1 << (10 * height as u32)) - 1))
+}
 
 impl PVec {
     /// Number of positions in this vector.
@@ -462,6 +847,20 @@ impl PVec {
         PVec(self.0 | (self.0 << 1 & RIGHT_50.0 & viable.0))
     }
 
+    /// All positions reachable from this vector by *any number* of movements
+    /// down, and no other movements.
+    #[must_use]
+    pub fn drop_fill(mut self, viable: PVec) -> PVec {
+        let mut next;
+        while {
+            next = self.or_down(viable);
+            self != next
+        } {
+            self = next;
+        }
+        self
+    }
+
     /// All positions reachable from this vector by *any number* of movements
     /// down, left, or right.
     #[must_use]
@@ -478,6 +877,33 @@ impl PVec {
         self
     }
 
+    /// Flood fill four vectors at once, each with its own viable positions.
+    ///
+    /// The result is the same as calling [`flood_fill`](PVec::flood_fill) on
+    /// each vector.  But the four vectors are moved in lockstep, as lanes of
+    /// one wide value, which the compiler can turn into SIMD instructions.
+    /// Lanes which finish early just stop changing.
+    pub fn flood_fill_x4(vecs: [PVec; 4], viable: [PVec; 4]) -> [PVec; 4] {
+        let mut lanes = vecs.map(|v| v.0);
+        let viable = viable.map(|v| v.0);
+
+        loop {
+            let mut next = [0; 4];
+            for i in 0..4 {
+                let x = lanes[i];
+                let x = x | (x >> 10 & viable[i]);
+                let x = x | (x >> 1 & LEFT_50.0 & viable[i]);
+                let x = x | (x << 1 & RIGHT_50.0 & viable[i]);
+                next[i] = x;
+            }
+
+            if next == lanes {
+                return lanes.map(PVec);
+            }
+            lanes = next;
+        }
+    }
+
     /// Check whether the provided position is in this set.
     pub const fn contains(self, col: i8, row: i8) -> bool {
         self.0 & (1 << (col + row * 10)) != 0
@@ -551,6 +977,16 @@ pub struct Collision {
     placeable_shift: u8,
 }
 
+/// Collision data like [`Collision`], but built at runtime, for any number of
+/// minoes.
+///
+/// See [`BoxedCollision::make`].
+pub struct BoxedCollision {
+    shifts: Vec<u8>,
+    mask: u64,
+    placeable_shift: u8,
+}
+
 /// Packed kick data for a single piece under a single rotation system.
 ///
 /// Takes the number of offsets for quarter-rotation kicks and half-rotation
@@ -565,8 +1001,78 @@ pub struct Collision {
 /// [orientation]: Orientation
 pub struct Kicks<const QUARTER: usize, const HALF: usize> {
     // TODO: could have better data locality breaking into individual rotations
-    rotates: ([[u8; QUARTER]; 4], [[u8; HALF]; 4], [[u8; QUARTER]; 4]),
-    masks: ([[u64; QUARTER]; 4], [[u64; HALF]; 4], [[u64; QUARTER]; 4]),
+    rotates: PerDirection<u8, QUARTER, HALF>,
+    masks: PerDirection<u64, QUARTER, HALF>,
+    /// The offsets this was made from, for [`kick_offsets`].
+    offsets: PerDirection<(i8, i8), QUARTER, HALF>,
+}
+
+/// Data for each kick of a [`Kicks`], as a tuple indexed by rotation direction
+/// (clockwise, half, counter-clockwise), then by initial orientation.
+type PerDirection<T, const QUARTER: usize, const HALF: usize> =
+    ([[T; QUARTER]; 4], [[T; HALF]; 4], [[T; QUARTER]; 4]);
+
+/// Kick offsets for one shape, in the conventional form used by published
+/// kick tables.  See [`kick_offsets`].
+///
+/// Each field is indexed by initial [orientation], and lists the offsets in
+/// the order they are tried.  An offset `(x, y)` moves the piece `x` columns
+/// right and `y` rows up from where it would be after rotating around its
+/// usual center, without kicking.
+///
+/// [orientation]: Orientation
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct KickOffsets {
+    pub cw: [Vec<(i8, i8)>; 4],
+    pub half: [Vec<(i8, i8)>; 4],
+    pub ccw: [Vec<(i8, i8)>; 4],
+}
+
+/// Packed kick data like [`Kicks`], but built at runtime, with any number of
+/// offsets for each rotation.
+///
+/// `rotates` and `masks` are indexed first by rotation direction (clockwise,
+/// half, counter-clockwise), then by initial [orientation], and finally by kick
+/// number.
+///
+/// [orientation]: Orientation
+#[derive(Clone, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub struct BoxedKicks {
+    rotates: [[Vec<u8>; 4]; 3],
+    masks: [[Vec<u64>; 4]; 3],
+}
+
+/// Kick offsets for one shape, as loaded at runtime.
+///
+/// Indexed first by rotation direction (clockwise, half, counter-clockwise),
+/// then by initial [orientation], and finally by kick number.
+///
+/// [orientation]: Orientation
+pub type ShapeOffsets = [Vec<Vec<(i8, i8)>>; 3];
+
+/// Kick data for every shape, loaded at runtime, for [`Physics::Custom`].
+///
+/// Offsets are in [`Piece`] coordinates, like the static tables such as
+/// [`SRS_JLSTZ`], and are packed into [`BoxedKicks`] once, when constructed.
+/// Start from [`from_physics`](CustomKicks::from_physics) to modify a
+/// built-in rotation system.
+#[derive(Clone, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub struct CustomKicks {
+    /// Indexed by [shape](Shape).
+    offsets: [ShapeOffsets; 7],
+    /// Packed from `offsets`, indexed by [shape](Shape).
+    kicks: [BoxedKicks; 7],
+}
+
+/// A user-defined piece shape, such as a pentomino, with its own collision and
+/// kick data.
+///
+/// Tetrominoes use the static [`COLLISION`] and kick tables.  Everything here is
+/// built at runtime instead.  See [`Polyomino::place`].
+pub struct Polyomino {
+    /// Collision data, indexed by [orientation](Orientation).
+    pub collision: [BoxedCollision; 4],
+    pub kicks: BoxedKicks,
 }
 
 /// Collision data for every tetromino.
@@ -624,16 +1130,22 @@ pub static COLLISION: [[Collision; 4]; 7] = [
     ],
 ];
 
-/// Kick data for I pieces under SRS.
+/// Kick data for I pieces under SRS.  Half rotations only turn in place,
+/// without kicking.
 #[rustfmt::skip]
-pub static SRS_I: Kicks<5, 0> = Kicks::make(
+pub static SRS_I: Kicks<5, 1> = Kicks::make(
     [
         [( 2, -2), ( 0, -2), ( 3, -2), ( 0, -3), ( 3,  0)],
         [(-2,  1), (-3,  1), ( 0,  1), (-3,  3), ( 0,  0)],
         [( 1, -1), ( 3, -1), ( 0, -1), ( 3,  0), ( 0, -3)],
         [(-1,  2), ( 0,  2), (-3,  2), ( 0,  0), (-3,  3)],
     ],
-    [[], [], [], []],
+    [
+        [( 0, -1)],
+        [(-1,  0)],
+        [( 0,  1)],
+        [( 1,  0)],
+    ],
     [
         [( 1, -2), ( 0, -2), ( 3, -2), ( 0,  0), ( 3, -3)],
         [(-2,  2), ( 0,  2), (-3,  2), ( 0,  3), (-3,  0)],
@@ -641,16 +1153,22 @@ pub static SRS_I: Kicks<5, 0> = Kicks::make(
         [(-1,  1), (-3,  1), ( 0,  1), (-3,  0), ( 0,  3)],
     ],
 );
-/// Kick data for J, L, S, T, and Z pieces under SRS.
+/// Kick data for J, L, S, T, and Z pieces under SRS.  Half rotations only turn
+/// in place, without kicking.
 #[rustfmt::skip]
-pub static SRS_JLSTZ: Kicks<5, 0> = Kicks::make(
+pub static SRS_JLSTZ: Kicks<5, 1> = Kicks::make(
     [
         [( 1, -1), ( 0, -1), ( 0,  0), ( 1, -3), ( 0, -3)],
         [(-1,  0), ( 0,  0), ( 0, -1), (-1,  2), ( 0,  2)],
         [( 0,  0), ( 1,  0), ( 1,  1), ( 0, -2), ( 1, -2)],
         [( 0,  1), (-1,  1), (-1,  0), ( 0,  3), (-1,  3)],
     ],
-    [[], [], [], []],
+    [
+        [( 0, -1)],
+        [(-1,  0)],
+        [( 0,  1)],
+        [( 1,  0)],
+    ],
     [
         [( 0, -1), ( 1, -1), ( 1,  0), ( 0, -3), ( 1, -3)],
         [(-1,  1), ( 0,  1), ( 0,  0), (-1,  3), ( 0,  3)],
@@ -662,8 +1180,8 @@ pub static SRS_JLSTZ: Kicks<5, 0> = Kicks::make(
 /// `Kicks<0, 0>` --- in a sense, O actually has no rotations at all.
 pub static SRS_O: Kicks<0, 0> = Kicks::make([[], [], [], []], [[], [], [], []], [[], [], [], []]);
 
-/// Kick data for I pieces under Jstris.  Identical to [`SRS_I`] but with two
-/// kick offsets for each half rotation.
+/// Kick data for I pieces under Jstris.  Identical to [`SRS_I`] but with a
+/// second kick offset for each half rotation.
 ///
 /// [`SRS_I`]: SRS_I
 #[rustfmt::skip]
@@ -688,7 +1206,7 @@ pub static JSTRIS_I: Kicks<5, 2> = Kicks::make(
     ],
 );
 /// Kick data for J, L, S, T, and Z pieces under Jstris.  Identical to
-/// [`SRS_JLSTZ`] but with two kick offsets for each half rotation.
+/// [`SRS_JLSTZ`] but with a second kick offset for each half rotation.
 ///
 /// [`SRS_JLSTZ`]: SRS_JLSTZ
 #[rustfmt::skip]
@@ -763,6 +1281,101 @@ pub static TETRIO_JLSTZ: Kicks<5, 6> = Kicks::make(
     ],
 );
 
+/// Report the kicks used for a shape under the given physics.
+///
+/// The tables in this module, like [`SRS_JLSTZ`], store offsets in terms of
+/// [`Piece`] coordinates, which are relative to the bottom left of each
+/// orientation's bounding box.  That folds the movement of the rotation itself
+/// into every offset.  This function takes that movement back out, so the
+/// result can be compared directly against published tables, where the first
+/// SRS offset is always `(0, 0)`.
+///
+/// O pieces have no kicks in the built-in physics, so every list is empty for
+/// them.
+pub fn kick_offsets(physics: Physics, shape: Shape) -> KickOffsets {
+    match (physics, shape) {
+        (Physics::Custom(custom), _) => conventional(shape, |direction, from| {
+            &custom.offsets[shape as usize][direction][from as usize]
+        }),
+
+        (_, Shape::O) => KickOffsets::default(),
+
+        (Physics::SRS, Shape::I) => SRS_I.conventional(shape),
+        (Physics::SRS, _) => SRS_JLSTZ.conventional(shape),
+
+        (Physics::Jstris, Shape::I) => JSTRIS_I.conventional(shape),
+        (Physics::Jstris, _) => JSTRIS_JLSTZ.conventional(shape),
+
+        (Physics::Tetrio, Shape::I) => TETRIO_I.conventional(shape),
+        (Physics::Tetrio, _) => TETRIO_JLSTZ.conventional(shape),
+    }
+}
+
+/// Take the rotation movement out of kick offsets in [`Piece`] coordinates,
+/// for [`kick_offsets`].
+///
+/// `offsets(direction, initial)` lists the offsets for a rotation direction
+/// (clockwise, half, counter-clockwise) from an initial orientation.
+fn conventional<'a>(
+    shape: Shape,
+    offsets: impl Fn(usize, Orientation) -> &'a [(i8, i8)],
+) -> KickOffsets {
+    use Orientation::*;
+
+    let convert = |direction: usize, from: Orientation, to: Orientation| {
+        let (shift_cols, shift_rows) = rotation_shift(shape, from, to);
+        offsets(direction, from)
+            .iter()
+            .map(|&(cols, rows)| (cols - shift_cols, rows - shift_rows))
+            .collect()
+    };
+
+    let mut result = KickOffsets::default();
+
+    for from in [North, East, South, West] {
+        let i = from as usize;
+        result.cw[i] = convert(0, from, from.cw());
+        result.half[i] = convert(1, from, from.half());
+        result.ccw[i] = convert(2, from, from.ccw());
+    }
+
+    result
+}
+
+/// How far the bottom left of a piece's bounding box moves when it rotates
+/// from `from` to `to` around its usual center, without kicking.
+///
+/// Pieces rotate within a square box:  3&times;3 for J, L, S, T, and Z, and
+/// 4&times;4 for I.  O pieces rotate in place, so they never move.
+fn rotation_shift(shape: Shape, from: Orientation, to: Orientation) -> (i8, i8) {
+    // Minoes facing north, as `(column, row)` within the box, from the bottom.
+    let (size, north): (i8, [(i8, i8); 4]) = match shape {
+        Shape::O => return (0, 0),
+        Shape::I => (4, [(0, 2), (1, 2), (2, 2), (3, 2)]),
+        Shape::J => (3, [(0, 2), (0, 1), (1, 1), (2, 1)]),
+        Shape::L => (3, [(2, 2), (0, 1), (1, 1), (2, 1)]),
+        Shape::S => (3, [(1, 2), (2, 2), (0, 1), (1, 1)]),
+        Shape::T => (3, [(1, 2), (0, 1), (1, 1), (2, 1)]),
+        Shape::Z => (3, [(0, 2), (1, 2), (1, 1), (2, 1)]),
+    };
+
+    let corner = |orientation: Orientation| {
+        let mut minoes = north;
+        for _ in 0..orientation as u8 {
+            // one clockwise turn
+            minoes = minoes.map(|(col, row)| (row, size - 1 - col));
+        }
+
+        let col = minoes.iter().map(|&(col, _)| col).min().unwrap();
+        let row = minoes.iter().map(|&(_, row)| row).min().unwrap();
+        (col, row)
+    };
+
+    let (from_col, from_row) = corner(from);
+    let (to_col, to_row) = corner(to);
+    (to_col - from_col, to_row - from_row)
+}
+
 impl Collision {
     /// Compute collision data for a single shape and orientation from the given
     /// mino coordinates.  The provided coordinates are for a piece at position
@@ -792,6 +1405,24 @@ impl Collision {
         }
     }
 
+    /// How far the board is shifted right for each mino, one shift per mino.
+    /// See the explanation on [`Collision`].
+    pub const fn shifts(&self) -> [u8; 4] {
+        self.shifts
+    }
+
+    /// Which positions keep the piece from wrapping around the right side of
+    /// the board.
+    pub const fn mask(&self) -> u64 {
+        self.mask
+    }
+
+    /// How far [`placeable`](Collision::placeable) shifts positions left and
+    /// back, to cut off those too high to place at.
+    pub const fn placeable_shift(&self) -> u8 {
+        self.placeable_shift
+    }
+
     /// Find which positions on the board are viable for this shape and
     /// orientation.  (In other words, all positions at which a piece could be
     /// placed if it were teleported in. See [here](crate::vector#how).)
@@ -805,6 +1436,27 @@ impl Collision {
         PVec(!collisions & self.mask)
     }
 
+    /// Check whether filling the given cells changes which positions are
+    /// viable for this shape and orientation.
+    ///
+    /// `viable` is the cached result of [`viable`](Collision::viable) for the
+    /// board before the change, and `added_cell` is a bit board, usually with
+    /// a single cell set.  Filling cells can only make positions unviable, so
+    /// this is true exactly when some viable position covers an added cell.
+    ///
+    /// Placements are found from the viable positions of all four
+    /// orientations together, since kicks connect them.  So placements cached
+    /// for a shape are still correct only if this returns false for each of
+    /// its orientations.
+    pub fn affected_by(&self, viable: PVec, added_cell: u64) -> bool {
+        let covering = (added_cell >> self.shifts[0])
+            | (added_cell >> self.shifts[1])
+            | (added_cell >> self.shifts[2])
+            | (added_cell >> self.shifts[3]);
+
+        (covering & viable.0) != 0
+    }
+
     /// Find which positions are placeable for this shape and orientation.  This
     /// will cut off positions from the top, *possibly even ones in bounds*,
     /// because if a piece were placed there, it might peek out the top of the
@@ -813,6 +1465,162 @@ impl Collision {
         let grounded = reachable.0 & !(reachable.0 << 10);
         PVec(grounded << self.placeable_shift >> self.placeable_shift)
     }
+
+    /// Find which positions are placeable, like
+    /// [`placeable`](Collision::placeable), but on a board `height` rows tall
+    /// instead of 4.
+    pub fn placeable_in(&self, reachable: PVec, height: u8) -> PVec {
+        // The shift is measured from a 4-row board.
+        let shift = self.placeable_shift as u32 + 40 - 10 * height as u32;
+        if shift >= 64 {
+            // The piece is taller than the board.
+            return PVec(0);
+        }
+
+        let grounded = reachable.0 & !(reachable.0 << 10);
+        PVec(grounded << shift >> shift)
+    }
+}
+
+impl BoxedCollision {
+    /// Compute collision data for a single shape and orientation, exactly like
+    /// [`Collision::make`], but with any number of minoes.
+    ///
+    /// If some mino is outside the bottom-left 10&times;4 cells, the piece
+    /// can never fit on the board, so it has no viable positions at all.
+    pub fn make(minoes: &[(u8, u8)]) -> BoxedCollision {
+        assert!(!minoes.is_empty());
+
+        if !minoes.iter().all(|&(col, row)| col < 10 && row < 4) {
+            return BoxedCollision {
+                shifts: Vec::new(),
+                mask: 0,
+                placeable_shift: 0,
+            };
+        }
+
+        let shifts = minoes.iter().map(|&(col, row)| col + row * 10).collect();
+
+        let row_mask = minoes
+            .iter()
+            .fold(FULL_10, |row_mask, &(col, _)| row_mask & (FULL_10 >> col));
+
+        let max_row = minoes.iter().map(|&(_, row)| row).max().unwrap();
+
+        BoxedCollision {
+            shifts,
+            mask: replicate_row(row_mask),
+            placeable_shift: 24 + 10 * max_row,
+        }
+    }
+
+    /// See [`Collision::shifts`].
+    pub fn shifts(&self) -> &[u8] {
+        &self.shifts
+    }
+
+    /// See [`Collision::mask`].
+    pub fn mask(&self) -> u64 {
+        self.mask
+    }
+
+    /// See [`Collision::placeable_shift`].
+    pub fn placeable_shift(&self) -> u8 {
+        self.placeable_shift
+    }
+
+    /// Find which positions on the board are viable.  See
+    /// [`Collision::viable`].
+    pub fn viable(&self, board: Board) -> PVec {
+        let collisions = self
+            .shifts
+            .iter()
+            .fold(0, |collisions, &shift| collisions | board.0 >> shift);
+
+        PVec(!collisions & self.mask)
+    }
+
+    /// Find which positions are placeable.  See [`Collision::placeable`].
+    pub fn placeable(&self, reachable: PVec) -> PVec {
+        let grounded = reachable.0 & !(reachable.0 << 10);
+        PVec(grounded << self.placeable_shift >> self.placeable_shift)
+    }
+
+    /// Cells covered by a piece at the given position.
+    fn cells_at(&self, position: u32) -> u64 {
+        self.shifts
+            .iter()
+            .fold(0, |cells, &shift| cells | 1 << (position + shift as u32))
+    }
+}
+
+impl Polyomino {
+    /// Define a piece from its minoes in each orientation, and its kick data.
+    ///
+    /// `minoes` is indexed by [orientation](Orientation).  As with
+    /// [`Collision::make`], coordinates are `(column, row)` for a piece at
+    /// position (0, 0).
+    pub fn new(minoes: [&[(u8, u8)]; 4], kicks: BoxedKicks) -> Polyomino {
+        Polyomino {
+            collision: minoes.map(BoxedCollision::make),
+            kicks,
+        }
+    }
+
+    /// Find all placeable positions of this piece on the given board, indexed
+    /// by orientation.
+    ///
+    /// This is the same search as [`Placements::place`], but there is no
+    /// [`Shape`] to describe the placed pieces, so only the raw positions are
+    /// returned.  Use [`place_boards`](Polyomino::place_boards) to get the
+    /// resulting boards.
+    pub fn place(&self, board: Board) -> [PVec; 4] {
+        let mut machine = PlacementMachine::new([
+            self.collision[0].viable(board),
+            self.collision[1].viable(board),
+            self.collision[2].viable(board),
+            self.collision[3].viable(board),
+        ]);
+
+        machine.run(&self.kicks);
+
+        [
+            self.collision[0].placeable(machine.reachable[0]),
+            self.collision[1].placeable(machine.reachable[1]),
+            self.collision[2].placeable(machine.reachable[2]),
+            self.collision[3].placeable(machine.reachable[3]),
+        ]
+    }
+
+    /// Place this piece in every placeable position on the given board.
+    ///
+    /// Iterates like [`Placements`]:  through orientations clockwise starting
+    /// from north, lowest position first.  Each board has full lines cleared,
+    /// as with [`Piece::place`].
+    pub fn place_boards(&self, board: Board) -> impl Iterator<Item = (Orientation, Board)> + '_ {
+        use Orientation::*;
+
+        let positions = self.place(board);
+
+        [North, East, South, West]
+            .iter()
+            .flat_map(move |&orientation| {
+                let collision = &self.collision[orientation as usize];
+                let mut remaining = positions[orientation as usize].0;
+
+                std::iter::from_fn(move || {
+                    if remaining == 0 {
+                        return None;
+                    }
+
+                    let position = remaining.trailing_zeros();
+                    remaining &= remaining - 1;
+
+                    let placed = Board(board.0 | collision.cells_at(position));
+                    Some((orientation, placed.clear_full_lines().0))
+                })
+            })
+    }
 }
 
 impl<const QUARTER: usize, const HALF: usize> Kicks<QUARTER, HALF> {
@@ -829,29 +1637,18 @@ impl<const QUARTER: usize, const HALF: usize> Kicks<QUARTER, HALF> {
         let mut half_masks = [[0; HALF]; 4];
         let mut ccw_masks = [[0; QUARTER]; 4];
 
-        pub const fn make_one((cols, rows): (i8, i8)) -> (u8, u64) {
-            debug_assert!(cols.abs() < 10);
-            debug_assert!(rows.abs() < 4);
-
-            let row_mask = shift_left_signed(FULL_10, cols) & FULL_10;
-            let board_mask = shift_left_signed(replicate_row(row_mask), rows * 10) & FULL_60;
-            let signed_shift = cols + rows * 10;
-
-            ((signed_shift + 64) as u8 % 64, board_mask)
-        }
-
         let mut i = 0;
         while i < 4 {
             let mut j = 0;
             while j < QUARTER {
-                (cw_rotates[i][j], cw_masks[i][j]) = make_one(cw_offsets[i][j]);
-                (ccw_rotates[i][j], ccw_masks[i][j]) = make_one(ccw_offsets[i][j]);
+                (cw_rotates[i][j], cw_masks[i][j]) = pack_kick(cw_offsets[i][j]);
+                (ccw_rotates[i][j], ccw_masks[i][j]) = pack_kick(ccw_offsets[i][j]);
                 j += 1;
             }
 
             j = 0;
             while j < HALF {
-                (half_rotates[i][j], half_masks[i][j]) = make_one(half_offsets[i][j]);
+                (half_rotates[i][j], half_masks[i][j]) = pack_kick(half_offsets[i][j]);
                 j += 1;
             }
 
@@ -861,9 +1658,30 @@ impl<const QUARTER: usize, const HALF: usize> Kicks<QUARTER, HALF> {
         Kicks {
             rotates: (cw_rotates, half_rotates, ccw_rotates),
             masks: (cw_masks, half_masks, ccw_masks),
+            offsets: (cw_offsets, half_offsets, ccw_offsets),
         }
     }
 
+    /// Unpack these kicks as [`KickOffsets`], for a given shape.  See
+    /// [`kick_offsets`].
+    fn conventional(&self, shape: Shape) -> KickOffsets {
+        conventional(shape, |direction, from| match direction {
+            0 => &self.offsets.0[from as usize],
+            1 => &self.offsets.1[from as usize],
+            _ => &self.offsets.2[from as usize],
+        })
+    }
+
+    /// Copy the offsets into vectors, in the form taken by
+    /// [`BoxedKicks::from_vecs`].
+    fn to_vecs(&self) -> ShapeOffsets {
+        [
+            self.offsets.0.iter().map(|kicks| kicks.to_vec()).collect(),
+            self.offsets.1.iter().map(|kicks| kicks.to_vec()).collect(),
+            self.offsets.2.iter().map(|kicks| kicks.to_vec()).collect(),
+        ]
+    }
+
     pub fn cw(&self, initial: Orientation, from: PVec, viable: PVec) -> PVec {
         Self::do_kicks(initial, from, viable, &self.rotates.0, &self.masks.0)
     }
@@ -901,6 +1719,174 @@ impl<const QUARTER: usize, const HALF: usize> Kicks<QUARTER, HALF> {
     }
 }
 
+impl BoxedKicks {
+    /// Pack kick data loaded at runtime, exactly like [`Kicks::make`].
+    ///
+    /// Each argument is indexed first by initial [orientation], then by kick
+    /// number, and must have exactly 4 entries.  Every offset must move less
+    /// than 10 columns and less than 4 rows.
+    ///
+    /// [orientation]: Orientation
+    pub fn from_vecs(
+        cw_offsets: Vec<Vec<(i8, i8)>>,
+        half_offsets: Vec<Vec<(i8, i8)>>,
+        ccw_offsets: Vec<Vec<(i8, i8)>>,
+    ) -> BoxedKicks {
+        let mut kicks = BoxedKicks {
+            rotates: Default::default(),
+            masks: Default::default(),
+        };
+
+        for (direction, offsets) in [cw_offsets, half_offsets, ccw_offsets].iter().enumerate() {
+            assert_eq!(offsets.len(), 4);
+
+            for (initial, offsets) in offsets.iter().enumerate() {
+                for &(cols, rows) in offsets {
+                    assert!(cols.abs() < 10);
+                    assert!(rows.abs() < 4);
+
+                    let (rotate, mask) = pack_kick((cols, rows));
+                    kicks.rotates[direction][initial].push(rotate);
+                    kicks.masks[direction][initial].push(mask);
+                }
+            }
+        }
+
+        kicks
+    }
+
+    pub fn cw(&self, initial: Orientation, from: PVec, viable: PVec) -> PVec {
+        self.do_kicks(0, initial, from, viable)
+    }
+
+    pub fn half(&self, initial: Orientation, from: PVec, viable: PVec) -> PVec {
+        self.do_kicks(1, initial, from, viable)
+    }
+
+    pub fn ccw(&self, initial: Orientation, from: PVec, viable: PVec) -> PVec {
+        self.do_kicks(2, initial, from, viable)
+    }
+
+    /// Same as [`Kicks::do_kicks`], but with slices.
+    fn do_kicks(&self, direction: usize, initial: Orientation, from: PVec, viable: PVec) -> PVec {
+        let rotates = &self.rotates[direction][initial as usize];
+        let masks = &self.masks[direction][initial as usize];
+
+        let mut from = from.0;
+        let mut to = 0;
+        let mask = viable.0;
+
+        for (&rotate, &kick_mask) in rotates.iter().zip(masks) {
+            let kicked = from.rotate_left(rotate as u32) & kick_mask & mask;
+            from ^= kicked.rotate_right(rotate as u32);
+            to |= kicked;
+        }
+
+        PVec(to)
+    }
+}
+
+impl CustomKicks {
+    /// Pack kick data for every shape.
+    ///
+    /// `offsets` is indexed by [shape](Shape).  Each entry holds clockwise,
+    /// half, and counter-clockwise offsets, with the same requirements as
+    /// [`BoxedKicks::from_vecs`].
+    pub fn new(offsets: [ShapeOffsets; 7]) -> CustomKicks {
+        let kicks = offsets
+            .clone()
+            .map(|[cw, half, ccw]| BoxedKicks::from_vecs(cw, half, ccw));
+
+        CustomKicks { offsets, kicks }
+    }
+
+    /// Copy the kicks a physics uses for every shape.
+    ///
+    /// O pieces get a single in-place offset for each rotation, which matches
+    /// [`Piece::rotate`].
+    pub fn from_physics(physics: Physics) -> CustomKicks {
+        if let Physics::Custom(custom) = physics {
+            return custom.clone();
+        }
+
+        let in_place = vec![vec![(0, 0)]; 4];
+
+        CustomKicks::new(Shape::ALL.map(|shape| match (physics, shape) {
+            (_, Shape::O) => [in_place.clone(), in_place.clone(), in_place.clone()],
+
+            (Physics::SRS, Shape::I) => SRS_I.to_vecs(),
+            (Physics::SRS, _) => SRS_JLSTZ.to_vecs(),
+
+            (Physics::Jstris, Shape::I) => JSTRIS_I.to_vecs(),
+            (Physics::Jstris, _) => JSTRIS_JLSTZ.to_vecs(),
+
+            (Physics::Tetrio, Shape::I) => TETRIO_I.to_vecs(),
+            (Physics::Tetrio, _) => TETRIO_JLSTZ.to_vecs(),
+
+            (Physics::Custom(_), _) => unreachable!(),
+        }))
+    }
+
+    /// Keep these kicks for the rest of the program, so that they can be used
+    /// in [`Physics::Custom`].
+    ///
+    /// `Physics` is `Copy`, so it can only borrow its kicks, and they are never
+    /// freed.  Leaking the same kicks twice returns the first copy, so loading
+    /// a rotation system again (say, each time a settings page is opened) uses
+    /// no more memory.  Each distinct table costs a few kilobytes.
+    pub fn leak(self) -> &'static CustomKicks {
+        static LEAKED: Mutex<Vec<&'static CustomKicks>> = Mutex::new(Vec::new());
+
+        let mut leaked = LEAKED.lock().unwrap();
+        if let Some(&existing) = leaked.iter().find(|&&existing| *existing == self) {
+            return existing;
+        }
+
+        let kicks = Box::leak(Box::new(self));
+        leaked.push(kicks);
+        kicks
+    }
+
+    /// The offsets for one shape, in a rotation direction (clockwise, half,
+    /// counter-clockwise) from an initial orientation.
+    pub(crate) fn offsets(
+        &self,
+        shape: Shape,
+        direction: usize,
+        initial: Orientation,
+    ) -> &[(i8, i8)] {
+        &self.offsets[shape as usize][direction][initial as usize]
+    }
+}
+
+impl<const QUARTER: usize, const HALF: usize> KickTable for Kicks<QUARTER, HALF> {
+    fn cw(&self, initial: Orientation, from: PVec, viable: PVec) -> PVec {
+        Kicks::cw(self, initial, from, viable)
+    }
+
+    fn half(&self, initial: Orientation, from: PVec, viable: PVec) -> PVec {
+        Kicks::half(self, initial, from, viable)
+    }
+
+    fn ccw(&self, initial: Orientation, from: PVec, viable: PVec) -> PVec {
+        Kicks::ccw(self, initial, from, viable)
+    }
+}
+
+impl KickTable for BoxedKicks {
+    fn cw(&self, initial: Orientation, from: PVec, viable: PVec) -> PVec {
+        BoxedKicks::cw(self, initial, from, viable)
+    }
+
+    fn half(&self, initial: Orientation, from: PVec, viable: PVec) -> PVec {
+        BoxedKicks::half(self, initial, from, viable)
+    }
+
+    fn ccw(&self, initial: Orientation, from: PVec, viable: PVec) -> PVec {
+        BoxedKicks::ccw(self, initial, from, viable)
+    }
+}
+
 impl std::fmt::Debug for PVec {
     /// This formatter prints position vectors as 6×10 boards.  This can't be
     /// directly typed back in to reproduce the vector, but it's often more
@@ -926,6 +1912,19 @@ impl std::fmt::Debug for PVec {
 
 // Various utility functions.
 
+/// Pack a single kick offset `(column, row)` into a rotation amount and a mask
+/// of positions which don't wrap around the board when rotated.
+const fn pack_kick((cols, rows): (i8, i8)) -> (u8, u64) {
+    debug_assert!(cols.abs() < 10);
+    debug_assert!(rows.abs() < 4);
+
+    let row_mask = shift_left_signed(FULL_10, cols) & FULL_10;
+    let board_mask = shift_left_signed(replicate_row(row_mask), rows * 10) & FULL_60;
+    let signed_shift = cols + rows * 10;
+
+    ((signed_shift + 64) as u8 % 64, board_mask)
+}
+
 /// Calculate `n << by` where `by` can be negative.
 const fn shift_left_signed(n: u64, by: i8) -> u64 {
     if by >= 0 {
@@ -1012,3 +2011,674 @@ impl std::ops::BitOr for Placements {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::collections::{HashMap, HashSet, VecDeque};
+
+    use crate::{
+        gameplay::{Board, Orientation, Physics, Piece, Rotation, Shape, PIECE_SHAPES},
+        vector::{
+            kick_offsets, BoxedKicks, CustomKicks, PVec, Placements, Polyomino, COLLISION, SRS_I,
+            SRS_JLSTZ,
+        },
+    };
+
+    /// A sample of boards, the same every time for a given seed:  a mix of
+    /// dense and sparse boards, from xorshift.
+    fn random_boards(seed: u64, n: usize) -> impl Iterator<Item = Board> {
+        let mut seed = seed;
+        let mut next = move || {
+            seed ^= seed << 13;
+            seed ^= seed >> 7;
+            seed ^= seed << 17;
+            seed
+        };
+
+        (0..n).map(move |i| {
+            let mut cells = next();
+            for _ in 0..i % 4 {
+                cells &= next();
+            }
+            Board(cells & 0xFFFFF_FFFFF)
+        })
+    }
+
+    /// Find placements the slow way, by searching through every move of a
+    /// single piece.
+    fn graph_placements(board: Board, shape: Shape, physics: Physics) -> HashSet<Piece> {
+        use Orientation::*;
+
+        let mut seen = HashSet::new();
+        let mut queue = Vec::new();
+
+        for orientation in [North, East, South, West] {
+            for col in 0..10 {
+                let piece = Piece {
+                    shape,
+                    col,
+                    row: 4,
+                    orientation,
+                };
+                if piece.in_bounds() {
+                    seen.insert(piece);
+                    queue.push(piece);
+                }
+            }
+        }
+
+        let mut placed = HashSet::new();
+
+        while let Some(piece) = queue.pop() {
+            if piece.can_place(board) {
+                placed.insert(piece);
+            }
+
+            for new_piece in [
+                piece.left(board),
+                piece.right(board),
+                piece.down(board),
+                piece.rotate(Rotation::Clockwise, board, physics),
+                piece.rotate(Rotation::CounterClockwise, board, physics),
+                piece.rotate(Rotation::Half, board, physics),
+            ] {
+                if seen.insert(new_piece) {
+                    queue.push(new_piece);
+                }
+            }
+        }
+
+        placed
+    }
+
+    /// Find hard-drop-only placements the slow way:  search every move above
+    /// the board, then drop.
+    fn graph_hard_drops(board: Board, shape: Shape, physics: Physics) -> HashSet<Piece> {
+        use Orientation::*;
+
+        let mut seen = HashSet::new();
+        let mut queue = Vec::new();
+
+        for orientation in [North, East, South, West] {
+            for col in 0..10 {
+                let piece = Piece {
+                    shape,
+                    col,
+                    row: 4,
+                    orientation,
+                };
+                if piece.in_bounds() {
+                    seen.insert(piece);
+                    queue.push(piece);
+                }
+            }
+        }
+
+        let mut placed = HashSet::new();
+
+        while let Some(piece) = queue.pop() {
+            let mut dropped = piece;
+            while let Some(lower) = dropped.try_down(board) {
+                dropped = lower;
+            }
+            if dropped.can_place(board) {
+                placed.insert(dropped);
+            }
+
+            for new_piece in [
+                piece.left(board),
+                piece.right(board),
+                piece.rotate(Rotation::Clockwise, board, physics),
+                piece.rotate(Rotation::CounterClockwise, board, physics),
+                piece.rotate(Rotation::Half, board, physics),
+            ] {
+                if new_piece.row >= 4 && seen.insert(new_piece) {
+                    queue.push(new_piece);
+                }
+            }
+        }
+
+        placed
+    }
+
+    #[test]
+    fn hard_drop_only_matches_gameplay() {
+        for board in random_boards(54321, 100) {
+            for physics in [Physics::SRS, Physics::Jstris, Physics::Tetrio] {
+                for shape in Shape::ALL {
+                    let vector: HashSet<Piece> = Placements::hard_drop_only(board, shape, physics)
+                        .map(|(piece, _)| piece)
+                        .collect();
+
+                    assert_eq!(
+                        vector,
+                        graph_hard_drops(board, shape, physics),
+                        "{:?} {:?} {:?}",
+                        board,
+                        shape,
+                        physics
+                    );
+                    assert!(Placements::place(board, shape, physics)
+                        .map(|(piece, _)| piece)
+                        .collect::<HashSet<Piece>>()
+                        .is_superset(&vector));
+                }
+            }
+        }
+    }
+
+    /// Find the fewest rotations to place each piece the slow way, by
+    /// searching through every move, with rotations costing one and other
+    /// moves free.
+    fn graph_rotation_costs(board: Board, shape: Shape, physics: Physics) -> HashMap<Piece, u8> {
+        let mut cost = HashMap::new();
+        let mut queue = VecDeque::new();
+
+        for row in [4, 5] {
+            for col in 0..10 {
+                let piece = Piece {
+                    shape,
+                    col,
+                    row,
+                    orientation: Orientation::North,
+                };
+                if piece.in_bounds() {
+                    cost.insert(piece, 0);
+                    queue.push_back((piece, 0));
+                }
+            }
+        }
+
+        let mut placed = HashMap::new();
+
+        while let Some((piece, c)) = queue.pop_front() {
+            if cost[&piece] < c {
+                continue;
+            }
+            if piece.can_place(board) {
+                let canonical = Piece {
+                    orientation: piece.orientation.canonical(shape),
+                    ..piece
+                };
+                let best = placed.entry(canonical).or_insert(c);
+                *best = (*best).min(c);
+            }
+
+            let moves = [piece.left(board), piece.right(board), piece.down(board)];
+            let rotations = [
+                piece.rotate(Rotation::Clockwise, board, physics),
+                piece.rotate(Rotation::CounterClockwise, board, physics),
+                piece.rotate(Rotation::Half, board, physics),
+            ];
+
+            for (new_piece, new_c) in moves
+                .iter()
+                .map(|&p| (p, c))
+                .chain(rotations.iter().map(|&p| (p, c + 1)))
+            {
+                if cost.get(&new_piece).is_none_or(|&old| new_c < old) {
+                    cost.insert(new_piece, new_c);
+                    if new_c == c {
+                        queue.push_front((new_piece, new_c));
+                    } else {
+                        queue.push_back((new_piece, new_c));
+                    }
+                }
+            }
+        }
+
+        placed
+    }
+
+    #[test]
+    fn srs_superset_matches_placements() {
+        for board in random_boards(97531, 100) {
+            for physics in Physics::ALL {
+                if !physics.is_srs_superset() {
+                    continue;
+                }
+
+                for shape in Shape::ALL {
+                    let srs: HashSet<Piece> = Placements::place(board, shape, Physics::SRS)
+                        .map(|(piece, _)| piece)
+                        .collect();
+                    let other: HashSet<Piece> = Placements::place(board, shape, physics)
+                        .map(|(piece, _)| piece)
+                        .collect();
+
+                    assert!(
+                        other.is_superset(&srs),
+                        "{:?} {:?} {:?}",
+                        board,
+                        shape,
+                        physics
+                    );
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn place_in_taller_board() {
+        // A well in the rightmost column, from row 1 to the top of a 5-row
+        // board.
+        let row: u64 = 0b0111111111;
+        let board = Board(0b1111111110 | row << 10 | row << 20 | row << 30 | row << 40);
+
+        let in_well = |placements: Placements| {
+            [Orientation::East, Orientation::West]
+                .iter()
+                .any(|&orientation| {
+                    placements.contains(Piece {
+                        shape: Shape::I,
+                        col: 9,
+                        row: 1,
+                        orientation,
+                    })
+                })
+        };
+
+        for physics in Physics::ALL {
+            assert!(in_well(Placements::place_in(board, Shape::I, physics, 5)));
+
+            // On a 4-row board, the I piece would stick out the top.
+            assert!(!in_well(Placements::place(board, Shape::I, physics)));
+        }
+
+        // Unlike iterating, listing pieces works on the taller board too.
+        assert!(Placements::place_in(board, Shape::I, Physics::SRS, 5)
+            .iter_placeable_pieces()
+            .any(|piece| (piece.col, piece.row) == (9, 1)));
+
+        assert_eq!(
+            Placements::place_in(board, Shape::I, Physics::SRS, 1).len(),
+            0
+        );
+        assert!(
+            Placements::place_in(Board(0b1111), Shape::T, Physics::SRS, 4)
+                == Placements::place(Board(0b1111), Shape::T, Physics::SRS)
+        );
+    }
+
+    #[test]
+    fn iter_placeable_pieces() {
+        let board = Board(0b0000011111_1000011111_1100111111);
+
+        for physics in Physics::ALL {
+            for shape in Shape::ALL {
+                let placements = Placements::place(board, shape, physics);
+                let with_boards: Vec<Piece> = placements.clone().map(|(piece, _)| piece).collect();
+                let pieces: Vec<Piece> = placements.iter_placeable_pieces().collect();
+                assert_eq!(pieces, with_boards);
+            }
+        }
+    }
+
+    #[test]
+    fn symmetric_difference() {
+        let mut differ = false;
+
+        for board in random_boards(13579, 100) {
+            for shape in Shape::ALL {
+                let srs = Placements::place(board, shape, Physics::SRS);
+                let tetrio = Placements::place(board, shape, Physics::Tetrio);
+                let (only_srs, only_tetrio) = srs.symmetric_difference(&tetrio);
+
+                let srs: HashSet<Piece> = srs.map(|(piece, _)| piece).collect();
+                let tetrio: HashSet<Piece> = tetrio.map(|(piece, _)| piece).collect();
+
+                assert_eq!(
+                    only_srs.map(|(piece, _)| piece).collect::<HashSet<_>>(),
+                    &srs - &tetrio
+                );
+                assert_eq!(
+                    only_tetrio.map(|(piece, _)| piece).collect::<HashSet<_>>(),
+                    &tetrio - &srs
+                );
+
+                differ |= srs != tetrio;
+            }
+        }
+
+        assert!(differ);
+    }
+
+    #[test]
+    fn rotation_cost_matches_gameplay() {
+        for board in random_boards(24680, 40) {
+            for physics in [Physics::SRS, Physics::Jstris, Physics::Tetrio] {
+                for shape in Shape::ALL {
+                    let placements = Placements::place(board, shape, physics);
+                    let expected = graph_rotation_costs(board, shape, physics);
+
+                    for (piece, _) in placements.clone() {
+                        let canonical = Piece {
+                            orientation: piece.orientation.canonical(shape),
+                            ..piece
+                        };
+                        assert_eq!(
+                            placements.rotation_cost(piece, physics),
+                            expected.get(&canonical).copied(),
+                            "{:?} {:?} {:?}",
+                            board,
+                            piece,
+                            physics
+                        );
+                    }
+                }
+            }
+        }
+
+        let t = |orientation| Piece {
+            shape: Shape::T,
+            col: 0,
+            row: 0,
+            orientation,
+        };
+        let srs = Placements::place(Board::empty(), Shape::T, Physics::SRS);
+        let tetrio = Placements::place(Board::empty(), Shape::T, Physics::Tetrio);
+
+        assert_eq!(
+            srs.rotation_cost(t(Orientation::North), Physics::SRS),
+            Some(0)
+        );
+        assert_eq!(
+            srs.rotation_cost(t(Orientation::East), Physics::SRS),
+            Some(1)
+        );
+        assert_eq!(
+            srs.rotation_cost(t(Orientation::South), Physics::SRS),
+            Some(1)
+        );
+        assert_eq!(
+            tetrio.rotation_cost(t(Orientation::South), Physics::Tetrio),
+            Some(1)
+        );
+        assert_eq!(
+            srs.rotation_cost(
+                Piece {
+                    row: 1,
+                    ..t(Orientation::North)
+                },
+                Physics::SRS
+            ),
+            None
+        );
+    }
+
+    #[test]
+    fn place_many_matches_place() {
+        // not a multiple of four, so the last batch is padded
+        let boards: Vec<Board> = random_boards(777, 103).collect();
+
+        for physics in [Physics::SRS, Physics::Jstris, Physics::Tetrio] {
+            for shape in Shape::ALL {
+                let one_by_one: Vec<Placements> = boards
+                    .iter()
+                    .map(|&board| Placements::place(board, shape, physics))
+                    .collect();
+
+                assert!(Placements::place_many(&boards, shape, physics) == one_by_one);
+            }
+        }
+    }
+
+    /// Not a real benchmark, but enough to check that batching is worth it.
+    /// Run with `cargo test --release -- --ignored --nocapture`.
+    #[test]
+    #[ignore]
+    fn place_many_timing() {
+        use std::{
+            hint::black_box,
+            time::{Duration, Instant},
+        };
+
+        let boards: Vec<Board> = random_boards(4242, 1 << 20).collect();
+
+        // Best of several runs, since the machine may be busy.
+        let time = |f: &dyn Fn() -> Vec<Placements>| {
+            (0..5)
+                .map(|_| {
+                    let start = Instant::now();
+                    black_box(f());
+                    start.elapsed()
+                })
+                .min()
+                .unwrap_or(Duration::ZERO)
+        };
+
+        let one_by_one = time(&|| {
+            black_box(&boards)
+                .iter()
+                .map(|&board| Placements::place(board, Shape::O, Physics::SRS))
+                .collect()
+        });
+        let batched = time(&|| Placements::place_many(black_box(&boards), Shape::O, Physics::SRS));
+
+        eprintln!(
+            "{} O boards:  place {:?}, place_many {:?}",
+            boards.len(),
+            one_by_one,
+            batched
+        );
+    }
+
+    #[test]
+    fn kick_offsets_match_published() {
+        use Orientation::*;
+
+        // From the SRS tables on the Tetris wiki, with y up.
+        let jlstz = kick_offsets(Physics::SRS, Shape::T);
+        assert_eq!(
+            jlstz.cw[North as usize],
+            [(0, 0), (-1, 0), (-1, 1), (0, -2), (-1, -2)]
+        );
+        assert_eq!(
+            jlstz.ccw[North as usize],
+            [(0, 0), (1, 0), (1, 1), (0, -2), (1, -2)]
+        );
+        assert_eq!(
+            jlstz.cw[West as usize],
+            [(0, 0), (-1, 0), (-1, -1), (0, 2), (-1, 2)]
+        );
+        assert!(jlstz.half.iter().all(|kicks| kicks == &[(0, 0)]));
+
+        let i = kick_offsets(Physics::SRS, Shape::I);
+        assert_eq!(
+            i.cw[North as usize],
+            [(0, 0), (-2, 0), (1, 0), (-2, -1), (1, 2)]
+        );
+        assert_eq!(
+            i.ccw[East as usize],
+            [(0, 0), (2, 0), (-1, 0), (2, 1), (-1, -2)]
+        );
+
+        // SRS+ for I pieces, as TETRIO uses.
+        let tetrio_i = kick_offsets(Physics::Tetrio, Shape::I);
+        assert_eq!(
+            tetrio_i.cw[North as usize],
+            [(0, 0), (1, 0), (-2, 0), (-2, -1), (1, 2)]
+        );
+        assert_eq!(tetrio_i.half[North as usize][0], (0, 0));
+
+        // One table covers five shapes, so they had better agree.
+        for physics in [Physics::SRS, Physics::Jstris, Physics::Tetrio] {
+            let t = kick_offsets(physics, Shape::T);
+            for shape in [Shape::J, Shape::L, Shape::S, Shape::Z] {
+                assert_eq!(kick_offsets(physics, shape), t);
+            }
+        }
+
+        assert_eq!(kick_offsets(Physics::SRS, Shape::O), Default::default());
+    }
+
+    #[test]
+    fn srs_half_rotates_in_place() {
+        let south = |physics| -> HashSet<Piece> {
+            Placements::place(Board::empty(), Shape::T, physics)
+                .map(|(piece, _)| piece)
+                .filter(|piece| piece.orientation == Orientation::South)
+                .collect()
+        };
+
+        let srs = south(Physics::SRS);
+        assert_eq!(srs.len(), 8);
+        assert_eq!(srs, south(Physics::Jstris));
+        assert_eq!(srs, south(Physics::Tetrio));
+
+        let t = Piece {
+            shape: Shape::T,
+            col: 4,
+            row: 0,
+            orientation: Orientation::South,
+        };
+        let flipped = Piece {
+            row: 1,
+            orientation: Orientation::North,
+            ..t
+        };
+        assert_eq!(t.half(Board::empty(), Physics::SRS), flipped);
+
+        // No kicks:  with the flipped position blocked, nothing happens.
+        let blocked = Board(1 << (2 * 10 + 5));
+        assert_eq!(t.half(blocked, Physics::SRS), t);
+        assert_ne!(t.half(blocked, Physics::Jstris), t);
+    }
+
+    #[test]
+    fn kicks_match_gameplay() {
+        for board in random_boards(12345, 200) {
+            for physics in [Physics::SRS, Physics::Jstris, Physics::Tetrio] {
+                for shape in Shape::ALL {
+                    let vector: HashSet<Piece> = Placements::place(board, shape, physics)
+                        .map(|(piece, _)| piece)
+                        .collect();
+                    let graph = graph_placements(board, shape, physics);
+
+                    assert_eq!(vector, graph, "{:?} {:?} {:?}", physics, shape, board);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn polyomino_matches_tetromino() {
+        for shape in [Shape::I, Shape::J, Shape::L, Shape::S, Shape::T, Shape::Z] {
+            let minoes: Vec<Vec<(u8, u8)>> = PIECE_SHAPES[shape as usize]
+                .iter()
+                .map(|&bits| {
+                    (0..40)
+                        .filter(|&i| bits & (1 << i) != 0)
+                        .map(|i| (i % 10, i / 10))
+                        .collect()
+                })
+                .collect();
+            let [cw, half, ccw] = match shape {
+                Shape::I => SRS_I.to_vecs(),
+                _ => SRS_JLSTZ.to_vecs(),
+            };
+            let polyomino = Polyomino::new(
+                [&minoes[0], &minoes[1], &minoes[2], &minoes[3]],
+                BoxedKicks::from_vecs(cw, half, ccw),
+            );
+
+            for board in random_boards(16180, 100) {
+                assert_eq!(
+                    polyomino.place(board),
+                    Placements::place(board, shape, Physics::SRS).positions,
+                    "{:?} {:?}",
+                    shape,
+                    board
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn polyomino_too_tall() {
+        // An I pentomino, which stands 5 rows tall on its side.
+        let flat: Vec<(u8, u8)> = (0..5).map(|col| (col, 0)).collect();
+        let tall: Vec<(u8, u8)> = (0..5).map(|row| (0, row)).collect();
+        let [cw, half, ccw] = SRS_I.to_vecs();
+        let polyomino = Polyomino::new(
+            [&flat, &tall, &flat, &tall],
+            BoxedKicks::from_vecs(cw, half, ccw),
+        );
+
+        for board in random_boards(27182, 40).chain([Board(0b1111100000)]) {
+            let positions = polyomino.place(board);
+            assert_eq!(positions[Orientation::East as usize], PVec(0));
+            assert_eq!(positions[Orientation::West as usize], PVec(0));
+
+            let boards: Vec<(Orientation, Board)> = polyomino.place_boards(board).collect();
+            let count: u32 = positions.iter().map(|p| p.0.count_ones()).sum();
+            assert_eq!(boards.len(), count as usize);
+
+            for (orientation, placed) in boards {
+                assert!(orientation == Orientation::North || orientation == Orientation::South);
+                assert_eq!(placed.0.count_ones(), board.0.count_ones() + 5);
+            }
+        }
+
+        // Filling the rest of the bottom row completes it, and it stays at the
+        // bottom.
+        let board = Board(0b1111100000);
+        assert!(polyomino
+            .place_boards(board)
+            .any(|(_, placed)| placed == Board(0b1111111111)));
+    }
+
+    #[test]
+    fn affected_by_matches_viable() {
+        for board in random_boards(31415, 40) {
+            for collision in COLLISION.iter().flatten() {
+                let viable = collision.viable(board);
+
+                for cell in 0..40 {
+                    let added = 1 << cell;
+                    let after = collision.viable(Board(board.0 | added));
+                    assert_eq!(
+                        collision.affected_by(viable, added),
+                        after != viable,
+                        "{:?} cell {}",
+                        board,
+                        cell
+                    );
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn custom_kicks_match_srs() {
+        let leaked = CustomKicks::from_physics(Physics::SRS).leak();
+        let again = CustomKicks::from_physics(Physics::SRS).leak();
+        assert!(std::ptr::eq(leaked, again));
+
+        let custom = Physics::Custom(leaked);
+
+        for shape in [Shape::I, Shape::J, Shape::L, Shape::S, Shape::T, Shape::Z] {
+            assert_eq!(
+                kick_offsets(custom, shape),
+                kick_offsets(Physics::SRS, shape)
+            );
+        }
+        let in_place = [vec![(0, 0)], vec![(0, 0)], vec![(0, 0)], vec![(0, 0)]];
+        let o = kick_offsets(custom, Shape::O);
+        assert_eq!(
+            (o.cw, o.half, o.ccw),
+            (in_place.clone(), in_place.clone(), in_place)
+        );
+        assert!(!custom.o_can_kick());
+
+        for board in random_boards(86420, 100) {
+            for shape in Shape::ALL {
+                let placements = Placements::place(board, shape, custom);
+                assert!(placements == Placements::place(board, shape, Physics::SRS));
+
+                let vector: HashSet<Piece> = placements.map(|(piece, _)| piece).collect();
+                assert_eq!(vector, graph_placements(board, shape, custom));
+            }
+        }
+    }
+}